@@ -0,0 +1,481 @@
+//! Backup of the variables of workspaces and variable sets before they are changed, and restoring
+//! them from the backup.
+//!
+//! Only non-sensitive variables are backed up, as the values of sensitive ones cannot be read, but
+//! the IDs of all variables and the sources of the values exported after the backup are recorded
+//! to tell the ones created since then by the export. The keys of sensitive variables are recorded
+//! as well, to report them as not restorable.
+//!
+//! Backups are saved to a per-user state directory by default, as they hold plaintext values.
+
+use std::collections::{HashMap, HashSet};
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::TfvarExportError,
+    terraform_api::{
+        client::TerraformClient,
+        models::{Resource, VarAttributes, VariableCategory},
+        register_variable::{TerraformVariableProperty, ValueEncoding, VariableTarget},
+    },
+    utils::{json_file, ownership},
+};
+
+/// Version of the format of backup files
+const BACKUP_VERSION: u32 = 1;
+
+/// Non-sensitive variable in a backup
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BackupVariable {
+    id: String,
+    key: String,
+    value: String,
+    category: VariableCategory,
+    hcl: bool,
+    description: Option<String>,
+}
+
+impl BackupVariable {
+    /// Property to restore the variable; `variable_id` is `None` if it no longer exists.
+    fn property(&self, variable_id: Option<String>) -> TerraformVariableProperty {
+        let encoding = match self.hcl {
+            true => ValueEncoding::RawHcl,
+            false => ValueEncoding::String,
+        };
+
+        TerraformVariableProperty::new(
+            variable_id,
+            self.key.to_owned(),
+            self.description.to_owned(),
+            serde_json::Value::String(self.value.to_owned()),
+        )
+        .with_category(self.category)
+        .with_encoding(encoding)
+    }
+}
+
+/// Sensitive variable in a backup, whose value is not in it
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SensitiveBackupVariable {
+    key: String,
+    category: VariableCategory,
+}
+
+/// Backup of the variables of a workspace or a variable set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetBackup {
+    name: String,
+    target: VariableTarget,
+    variables: Vec<BackupVariable>,
+    #[serde(default)]
+    sensitive_variables: Vec<SensitiveBackupVariable>,
+    /// IDs of all the variables including sensitive ones
+    variable_ids: Vec<String>,
+}
+
+impl TargetBackup {
+    /// Backup of the non-sensitive ones of `existing_variables` of `target` named `name`, and the
+    /// keys of the sensitive ones.
+    pub fn new(
+        name: String,
+        target: VariableTarget,
+        existing_variables: &[Resource<VarAttributes>],
+    ) -> Self {
+        let variables = existing_variables
+            .iter()
+            .filter(|val| !val.get_attributes().is_sensitive())
+            .map(|val| {
+                let attributes = val.get_attributes();
+                BackupVariable {
+                    id: val.get_id().to_owned(),
+                    key: attributes.get_key().to_owned(),
+                    value: attributes.get_value().clone().unwrap_or_default(),
+                    category: attributes.get_category(),
+                    hcl: attributes.is_hcl(),
+                    description: attributes.get_description().clone(),
+                }
+            })
+            .collect();
+        let sensitive_variables = existing_variables
+            .iter()
+            .filter(|val| val.get_attributes().is_sensitive())
+            .map(|val| SensitiveBackupVariable {
+                key: val.get_attributes().get_key().to_owned(),
+                category: val.get_attributes().get_category(),
+            })
+            .collect();
+        let variable_ids = existing_variables
+            .iter()
+            .map(|val| val.get_id().to_owned())
+            .collect();
+
+        Self {
+            name,
+            target,
+            variables,
+            sensitive_variables,
+            variable_ids,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_target(&self) -> &VariableTarget {
+        &self.target
+    }
+
+    pub fn get_variables(&self) -> &[BackupVariable] {
+        &self.variables
+    }
+
+    pub fn get_sensitive_variables(&self) -> &[SensitiveBackupVariable] {
+        &self.sensitive_variables
+    }
+}
+
+/// Backup of the targets taken at once, saved to a file named after the time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    version: u32,
+    /// RFC 3339 in UTC
+    created_at: String,
+    /// Sources in the ownership markers of the values exported after the backup was taken
+    sources: Vec<String>,
+    targets: Vec<TargetBackup>,
+}
+
+impl Backup {
+    pub fn new(targets: Vec<TargetBackup>, sources: Vec<String>) -> Self {
+        Self {
+            version: BACKUP_VERSION,
+            created_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            sources,
+            targets,
+        }
+    }
+
+    pub fn get_targets(&self) -> &[TargetBackup] {
+        &self.targets
+    }
+
+    pub fn get_sources(&self) -> &[String] {
+        &self.sources
+    }
+
+    /// Save the backup to `dir`, which is created if it does not exist, and return the path.
+    ///
+    /// The file is named after the time the backup was taken, such as
+    /// `tfvar-export-backup-20240101T000000000Z.json`, and readable only by the owner on Unix.
+    pub fn save(&self, dir: &str) -> Result<String, TfvarExportError> {
+        std::fs::create_dir_all(dir).map_err(|source| TfvarExportError::Io {
+            path: dir.to_string(),
+            source,
+        })?;
+        let created_at = chrono::DateTime::parse_from_rfc3339(&self.created_at)
+            .map_err(|err| TfvarExportError::Parse(err.to_string()))?;
+        let path = std::path::Path::new(dir).join(format!(
+            "tfvar-export-backup-{}.json",
+            created_at.format("%Y%m%dT%H%M%S%3fZ")
+        ));
+        let path = path.to_string_lossy().to_string();
+
+        json_file::save(&path, self)?;
+
+        Ok(path)
+    }
+
+    /// Load a backup saved by [`Backup::save`].
+    pub fn load(path: &str) -> Result<Self, TfvarExportError> {
+        let backup: Self = json_file::load(path)?;
+        if backup.version != BACKUP_VERSION {
+            return Err(TfvarExportError::Validation(format!(
+                "`{}`: Unsupported backup version {}, expected {}.",
+                path, backup.version, BACKUP_VERSION
+            )));
+        }
+
+        Ok(backup)
+    }
+}
+
+/// Default directory of backups: `tfvar-export/backups` in `$XDG_STATE_HOME`, `~/.local/state`
+/// or `%LOCALAPPDATA%`.
+///
+/// Fails if none of them is known, so that backups are never left in the current directory.
+pub fn default_backup_dir() -> Result<String, TfvarExportError> {
+    let env = |name| std::env::var_os(name).filter(|val| !val.is_empty());
+    let state_dir = env("XDG_STATE_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| env("HOME").map(|val| std::path::Path::new(&val).join(".local/state")))
+        .or_else(|| env("LOCALAPPDATA").map(std::path::PathBuf::from))
+        .ok_or_else(|| {
+            TfvarExportError::Config(String::from(
+                "`--backup-dir` is required as the home directory is unknown.",
+            ))
+        })?;
+
+    Ok(state_dir
+        .join("tfvar-export")
+        .join("backups")
+        .to_string_lossy()
+        .to_string())
+}
+
+/// Read the variables of the targets `(name, target)` and back them up, processing up to
+/// `concurrency` (at least one) targets at a time.
+///
+/// `sources` are the sources of the values to be exported, whose variables created since then are
+/// deleted on restore. Fails if any of the targets cannot be read, so that nothing is changed
+/// without a backup.
+pub async fn take_backup(
+    client: &TerraformClient,
+    targets: &[(String, VariableTarget)],
+    sources: Vec<String>,
+    concurrency: usize,
+) -> Result<Backup, TfvarExportError> {
+    let backups: Vec<Result<TargetBackup, TfvarExportError>> = futures::stream::iter(targets)
+        .map(|(name, target)| async move {
+            let existing_variables = client.get_variables(target).await?;
+            Ok(TargetBackup::new(
+                name.to_owned(),
+                target.to_owned(),
+                &existing_variables,
+            ))
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+
+    Ok(Backup::new(
+        backups.into_iter().collect::<Result<_, _>>()?,
+        sources,
+    ))
+}
+
+/// Changes to restore a workspace or a variable set to the backup
+#[derive(Debug)]
+pub struct RestorePlan {
+    target: VariableTarget,
+    /// Names of the variables to be created again
+    created: Vec<String>,
+    /// Names of the variables to be updated
+    updated: Vec<String>,
+    /// Names of the variables to be deleted
+    deleted: Vec<String>,
+    unchanged: Vec<String>,
+    /// Names of the sensitive variables in the backup, which are left as they are
+    not_restorable: Vec<String>,
+    vars_new: Vec<TerraformVariableProperty>,
+    vars_existing: Vec<TerraformVariableProperty>,
+    var_ids_delete: Vec<String>,
+}
+
+impl RestorePlan {
+    pub fn get_created(&self) -> &[String] {
+        &self.created
+    }
+
+    pub fn get_updated(&self) -> &[String] {
+        &self.updated
+    }
+
+    pub fn get_deleted(&self) -> &[String] {
+        &self.deleted
+    }
+
+    pub fn get_unchanged(&self) -> &[String] {
+        &self.unchanged
+    }
+
+    pub fn get_not_restorable(&self) -> &[String] {
+        &self.not_restorable
+    }
+
+    /// Whether restoring writes anything.
+    pub fn has_changes(&self) -> bool {
+        !self.vars_new.is_empty()
+            || !self.vars_existing.is_empty()
+            || !self.var_ids_delete.is_empty()
+    }
+}
+
+/// Plan restoring the variables of the target to the backup without writing anything.
+///
+/// Variables deleted since the backup was taken are to be created again and the changed ones are
+/// to be updated. Variables created since then are to be deleted if they have the marker of one of
+/// `sources`, the sources exported after the backup, and the others, such as ones exported from
+/// another source, are left as they are, and so are the ones restored from the backup even if they
+/// were deleted and created again since then. Sensitive variables in the backup are left as they
+/// are and reported as not restorable, as their values are not in it.
+pub async fn plan_restore(
+    client: &TerraformClient,
+    backup: &TargetBackup,
+    sources: &[String],
+) -> Result<RestorePlan, TfvarExportError> {
+    let target = backup.get_target();
+    log::info!("Planning to restore {}", target);
+    let existing_variables = client.get_variables(target).await?;
+    let existing_variables_map: HashMap<(&str, VariableCategory), &Resource<VarAttributes>> =
+        existing_variables
+            .iter()
+            .map(|val| {
+                (
+                    (
+                        val.get_attributes().get_key(),
+                        val.get_attributes().get_category(),
+                    ),
+                    val,
+                )
+            })
+            .collect();
+
+    let mut plan = RestorePlan {
+        target: target.to_owned(),
+        created: Vec::new(),
+        updated: Vec::new(),
+        deleted: Vec::new(),
+        unchanged: Vec::new(),
+        not_restorable: Vec::new(),
+        vars_new: Vec::new(),
+        vars_existing: Vec::new(),
+        var_ids_delete: Vec::new(),
+    };
+    // IDs of the existing variables restored from the backup, which are never deleted even if
+    // they were deleted and created again since then
+    let mut restored_ids: HashSet<&str> = HashSet::new();
+    for val in backup.get_variables() {
+        match existing_variables_map.get(&(val.key.as_str(), val.category)) {
+            None => {
+                plan.vars_new.push(val.property(None));
+                plan.created.push(val.key.to_owned());
+            },
+            Some(existing) => {
+                restored_ids.insert(existing.get_id());
+                let attributes = existing.get_attributes();
+                // A variable made sensitive since then cannot be made non-sensitive again.
+                let property = val
+                    .property(Some(existing.get_id().to_owned()))
+                    .with_sensitive(attributes.is_sensitive());
+                if property.is_up_to_date(attributes) {
+                    plan.unchanged.push(val.key.to_owned());
+                } else {
+                    plan.vars_existing.push(property);
+                    plan.updated.push(val.key.to_owned());
+                }
+            },
+        }
+    }
+
+    // Sensitive ones are left as they are, even if they were created again since then.
+    for val in backup.get_sensitive_variables() {
+        if let Some(existing) = existing_variables_map.get(&(val.key.as_str(), val.category)) {
+            restored_ids.insert(existing.get_id());
+        }
+        plan.not_restorable.push(val.key.to_owned());
+    }
+
+    for val in existing_variables.iter() {
+        let attributes = val.get_attributes();
+        let is_exported = ownership::marker_source(attributes.get_description().as_deref())
+            .is_some_and(|source| sources.iter().any(|val| val == source));
+        let is_new = !backup.variable_ids.iter().any(|id| id == val.get_id())
+            && !restored_ids.contains(val.get_id());
+        if is_new && is_exported {
+            plan.var_ids_delete.push(val.get_id().to_owned());
+            plan.deleted.push(attributes.get_key().to_owned());
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Restore the variables of the target as planned by [`plan_restore`].
+pub async fn apply_restore(
+    client: &TerraformClient,
+    plan: &RestorePlan,
+) -> Result<(), TfvarExportError> {
+    log::info!("Restoring {}", plan.target);
+    if !plan.vars_new.is_empty() {
+        client.create_variable(&plan.target, &plan.vars_new).await?;
+    }
+    if !plan.vars_existing.is_empty() {
+        client
+            .update_variable(&plan.target, &plan.vars_existing)
+            .await?;
+    }
+    if !plan.var_ids_delete.is_empty() {
+        client
+            .delete_variable(&plan.target, &plan.var_ids_delete)
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_target_backup() {
+        let existing_variables: Vec<Resource<VarAttributes>> = serde_json::from_value(json!([
+            {
+                "id": "var-1",
+                "type": "vars",
+                "attributes": {
+                    "key": "tags",
+                    "value": "{ env = \"dev\" }",
+                    "description": "description",
+                    "category": "terraform",
+                    "hcl": true
+                }
+            },
+            {
+                "id": "var-2",
+                "type": "vars",
+                "attributes": {
+                    "key": "SECRET",
+                    "value": null,
+                    "category": "env",
+                    "sensitive": true
+                }
+            }
+        ]))
+        .unwrap();
+        let backup = TargetBackup::new(
+            String::from("ws"),
+            VariableTarget::Workspace(String::from("ws-1")),
+            &existing_variables,
+        );
+
+        // Sensitive variables are not backed up.
+        assert_eq!(
+            serde_json::to_value(backup.get_variables()).unwrap(),
+            json!([{
+                "id": "var-1",
+                "key": "tags",
+                "value": "{ env = \"dev\" }",
+                "category": "terraform",
+                "hcl": true,
+                "description": "description"
+            }])
+        );
+        // but their keys and IDs are recorded.
+        assert_eq!(backup.get_sensitive_variables(), [SensitiveBackupVariable {
+            key: String::from("SECRET"),
+            category: VariableCategory::Env,
+        }]);
+        assert_eq!(backup.variable_ids, vec!["var-1", "var-2"]);
+
+        // Restored as it is.
+        let property = backup.get_variables()[0].property(Some(String::from("var-1")));
+        assert_eq!(property.encode(), (String::from("{ env = \"dev\" }"), true));
+        assert!(property.is_up_to_date(existing_variables[0].get_attributes()));
+    }
+}
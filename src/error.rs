@@ -0,0 +1,177 @@
+//! Error type of tfvar-export.
+
+use serde::Deserialize;
+
+/// An element of JSON:API `errors[]`.
+///
+/// **API Reference:** https://developer.hashicorp.com/terraform/cloud-docs/api-docs#response-codes
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ApiErrorDetail {
+    status: Option<String>,
+    title: Option<String>,
+    detail: Option<String>,
+}
+
+impl std::fmt::Display for ApiErrorDetail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.title, &self.detail) {
+            (Some(title), Some(detail)) => write!(f, "{}: {}", title, detail),
+            (Some(val), None) | (None, Some(val)) => write!(f, "{}", val),
+            (None, None) => write!(f, "{}", self.status.as_deref().unwrap_or("unknown error")),
+        }
+    }
+}
+
+/// Errors of tfvar-export
+#[derive(Debug)]
+pub enum TfvarExportError {
+    /// The API responded with an unexpected status code.
+    Api {
+        method: String,
+        path: String,
+        status: u16,
+        errors: Vec<ApiErrorDetail>,
+    },
+    /// The API request could not be sent or its response could not be read.
+    Http(reqwest::Error),
+    /// A file could not be read or written.
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    /// A file or an API response could not be parsed.
+    Parse(String),
+    /// An input such as the export list is invalid.
+    Validation(String),
+    /// A command-line option or an environment variable is missing or invalid.
+    Config(String),
+    /// Some of the target workspaces failed; each error has already been reported.
+    WorkspacesFailed(Vec<String>),
+    /// Some of the targets failed to be restored; each error has already been reported.
+    RestoreFailed(Vec<String>),
+    /// The variables of the targets have changed since the saved plan was made.
+    StalePlan(Vec<String>),
+}
+
+impl TfvarExportError {
+    /// Build an `Api` error from a response with an unexpected status code.
+    ///
+    /// `errors[]` is read from the body if the body is a JSON:API error document.
+    pub async fn from_response(method: &str, path: &str, response: reqwest::Response) -> Self {
+        #[derive(Deserialize)]
+        struct ErrorDocument {
+            errors: Vec<ApiErrorDetail>,
+        }
+
+        let status = response.status().as_u16();
+        let errors = match response.text().await {
+            Ok(body) => match serde_json::from_str::<ErrorDocument>(&body) {
+                Ok(doc) => doc.errors,
+                Err(_) => Vec::new(),
+            },
+            Err(_) => Vec::new(),
+        };
+
+        TfvarExportError::Api {
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            errors,
+        }
+    }
+}
+
+impl std::fmt::Display for TfvarExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TfvarExportError::Api {
+                method,
+                path,
+                status,
+                errors,
+            } => {
+                write!(
+                    f,
+                    "API request `{} {}` failed with status {}",
+                    method, path, status
+                )?;
+                if !errors.is_empty() {
+                    let details: Vec<String> = errors.iter().map(|val| val.to_string()).collect();
+                    write!(f, ": {}", details.join("; "))?;
+                }
+                match status {
+                    401 => write!(f, " (check `TFVE_TOKEN`)"),
+                    404 => write!(
+                        f,
+                        " (check the organization, workspace names and the token permissions)"
+                    ),
+                    _ => Ok(()),
+                }
+            },
+            TfvarExportError::Http(err) => write!(f, "HTTP request failed: {}", err),
+            TfvarExportError::Io { path, source } => write!(f, "`{}`: {}", path, source),
+            TfvarExportError::Parse(msg) => write!(f, "Parse error: {}", msg),
+            TfvarExportError::Validation(msg) => write!(f, "Invalid input: {}", msg),
+            TfvarExportError::Config(msg) => write!(f, "Configuration error: {}", msg),
+            TfvarExportError::WorkspacesFailed(workspaces) => write!(
+                f,
+                "Failed to export to workspace(s): {}",
+                workspaces.join(", ")
+            ),
+            TfvarExportError::RestoreFailed(targets) => {
+                write!(f, "Failed to restore target(s): {}", targets.join(", "))
+            },
+            TfvarExportError::StalePlan(targets) => write!(
+                f,
+                "The variables have changed since the plan was made, make a new plan: {}",
+                targets.join("; ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TfvarExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TfvarExportError::Http(err) => Some(err),
+            TfvarExportError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for TfvarExportError {
+    fn from(err: reqwest::Error) -> Self {
+        TfvarExportError::Http(err)
+    }
+}
+
+impl From<serde_json::Error> for TfvarExportError {
+    fn from(err: serde_json::Error) -> Self {
+        TfvarExportError::Parse(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_api_error() {
+        let err = TfvarExportError::Api {
+            method: String::from("POST"),
+            path: String::from("/api/v2/workspaces/ws-1/vars"),
+            status: 422,
+            errors: vec![ApiErrorDetail {
+                status: Some(String::from("422")),
+                title: Some(String::from("invalid attribute")),
+                detail: Some(String::from("Key has already been taken")),
+            }],
+        };
+        assert_eq!(
+            err.to_string(),
+            "API request `POST /api/v2/workspaces/ws-1/vars` failed with status 422: invalid \
+             attribute: Key has already been taken"
+        );
+    }
+}
@@ -0,0 +1,496 @@
+//! Export values to the target workspaces and variable sets.
+
+use std::collections::HashSet;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::TfvarExportError,
+    plan::{apply_plan, plan_target, PlannedVariable, TargetPlan, WorkspacePlan},
+    terraform_api::{
+        client::TerraformClient,
+        get_workspaces::TerraformWorkspace,
+        register_variable::{TerraformVariableRegistrationResult, VariableTarget},
+    },
+    utils::{
+        construct_export_value::ExportValue,
+        name_pattern::NamePattern,
+        ownership::OwnershipMarker,
+    },
+};
+
+/// Workspace to export values to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetWorkspace {
+    workspace_name: String,
+    workspace_id: String,
+}
+
+impl TargetWorkspace {
+    pub fn new(workspace_name: String, workspace_id: String) -> Self {
+        Self {
+            workspace_name,
+            workspace_id,
+        }
+    }
+
+    pub fn get_workspace_name(&self) -> &str {
+        &self.workspace_name
+    }
+
+    pub fn get_workspace_id(&self) -> &str {
+        &self.workspace_id
+    }
+}
+
+/// Criteria to select the workspaces to export values to
+#[derive(Debug, Default)]
+pub struct WorkspaceSelector {
+    workspace_names: Vec<String>,
+    project_names: Vec<String>,
+    patterns: Vec<NamePattern>,
+    /// IDs of the workspaces having the tags; `None` unless tags are specified.
+    tagged_workspace_ids: Option<HashSet<String>>,
+    excluded_names: Vec<String>,
+}
+
+impl WorkspaceSelector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_workspace_names(mut self, workspace_names: Vec<String>) -> Self {
+        self.workspace_names = workspace_names;
+        self
+    }
+
+    pub fn with_project_names(mut self, project_names: Vec<String>) -> Self {
+        self.project_names = project_names;
+        self
+    }
+
+    pub fn with_patterns(mut self, patterns: Vec<NamePattern>) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    pub fn with_tagged_workspace_ids(mut self, tagged_workspace_ids: HashSet<String>) -> Self {
+        self.tagged_workspace_ids = Some(tagged_workspace_ids);
+        self
+    }
+
+    pub fn with_excluded_names(mut self, excluded_names: Vec<String>) -> Self {
+        self.excluded_names = excluded_names;
+        self
+    }
+
+    /// Whether no workspaces are selected by any criteria.
+    pub fn is_empty(&self) -> bool {
+        self.workspace_names.is_empty()
+            && self.project_names.is_empty()
+            && self.patterns.is_empty()
+            && self.tagged_workspace_ids.is_none()
+    }
+
+    /// Resolve the workspaces out of `workspaces`.
+    ///
+    /// Workspaces are specified by name or ID. The workspaces of `workspace_names` come first in
+    /// the given order, followed by every workspace of the projects of `project_names`, and then
+    /// the workspaces matching any of `patterns` and having the tags; if only one of the two is
+    /// specified, it alone decides. Workspaces of `excluded_names` are removed, and unknown
+    /// workspaces and projects are skipped with a warning.
+    pub fn resolve(&self, workspaces: &[TerraformWorkspace]) -> Vec<TargetWorkspace> {
+        let mut resolved: Vec<&TerraformWorkspace> = Vec::new();
+        for name in self.workspace_names.iter() {
+            match workspaces
+                .iter()
+                .find(|val| val.get_workspace_name() == name || val.get_workspace_id() == name)
+            {
+                Some(workspace) => resolved.push(workspace),
+                None => log::warn!("{}: No such workspace, skipping.", name),
+            }
+        }
+        for name in self.project_names.iter() {
+            let mut project_workspaces = workspaces
+                .iter()
+                .filter(|val| val.get_project_name() == Some(name.as_str()))
+                .peekable();
+            if project_workspaces.peek().is_none() {
+                log::warn!(
+                    "{}: No such project or no workspaces in it, skipping.",
+                    name
+                );
+            }
+            resolved.extend(project_workspaces);
+        }
+        if !self.patterns.is_empty() || self.tagged_workspace_ids.is_some() {
+            let mut matched = workspaces
+                .iter()
+                .filter(|val| {
+                    self.patterns.is_empty()
+                        || self
+                            .patterns
+                            .iter()
+                            .any(|pattern| pattern.is_match(val.get_workspace_name()))
+                })
+                .filter(|val| match &self.tagged_workspace_ids {
+                    Some(ids) => ids.contains(val.get_workspace_id()),
+                    None => true,
+                })
+                .peekable();
+            if matched.peek().is_none() {
+                let patterns: Vec<&str> =
+                    self.patterns.iter().map(|val| val.get_pattern()).collect();
+                log::warn!(
+                    "No workspaces match the patterns [{}] and the tags, skipping.",
+                    patterns.join(", ")
+                );
+            }
+            resolved.extend(matched);
+        }
+
+        let mut result: Vec<TargetWorkspace> = Vec::new();
+        for workspace in resolved {
+            let duplicated = result
+                .iter()
+                .any(|val| val.get_workspace_id() == workspace.get_workspace_id());
+            let excluded = self.excluded_names.iter().any(|val| {
+                val == workspace.get_workspace_name() || val == workspace.get_workspace_id()
+            });
+            if !duplicated && !excluded {
+                result.push(TargetWorkspace::new(
+                    workspace.get_workspace_name().to_string(),
+                    workspace.get_workspace_id().to_string(),
+                ));
+            }
+        }
+
+        result
+    }
+}
+
+/// Options of exporting values
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Marker of the variables exported from the source
+    marker: OwnershipMarker,
+    allow_update: bool,
+    adopt: bool,
+    prune: bool,
+}
+
+impl ExportOptions {
+    /// Options to export values from `source`, which is recorded in the ownership marker.
+    pub fn new(source: &str) -> Self {
+        Self {
+            marker: OwnershipMarker::new(source),
+            allow_update: false,
+            adopt: false,
+            prune: false,
+        }
+    }
+
+    /// Update existing variables.
+    pub fn with_allow_update(mut self, allow_update: bool) -> Self {
+        self.allow_update = allow_update;
+        self
+    }
+
+    /// Update existing variables not managed from the source as well and take them over.
+    pub fn with_adopt(mut self, adopt: bool) -> Self {
+        self.adopt = adopt;
+        self
+    }
+
+    /// Delete managed variables no longer exported.
+    pub fn with_prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    pub fn get_marker(&self) -> &OwnershipMarker {
+        &self.marker
+    }
+
+    pub fn is_allow_update(&self) -> bool {
+        self.allow_update
+    }
+
+    pub fn is_adopt(&self) -> bool {
+        self.adopt
+    }
+
+    pub fn is_prune(&self) -> bool {
+        self.prune
+    }
+}
+
+/// Result of exporting values to a workspace or a variable set
+#[derive(Debug)]
+pub struct ExportResult {
+    created: Vec<TerraformVariableRegistrationResult>,
+    /// Variables updated as planned
+    updated: Vec<PlannedVariable>,
+    /// Names of the existing variables already up to date
+    unchanged: Vec<String>,
+    /// Names of the existing variables ignored because update is not allowed
+    ignored: Vec<String>,
+    /// Names of the existing variables not updated because they are not managed from the source
+    not_owned: Vec<String>,
+    /// Names of the managed variables deleted because they are no longer exported
+    pruned: Vec<String>,
+}
+
+impl ExportResult {
+    pub fn new(
+        created: Vec<TerraformVariableRegistrationResult>,
+        updated: Vec<PlannedVariable>,
+        unchanged: Vec<String>,
+        ignored: Vec<String>,
+        not_owned: Vec<String>,
+        pruned: Vec<String>,
+    ) -> Self {
+        Self {
+            created,
+            updated,
+            unchanged,
+            ignored,
+            not_owned,
+            pruned,
+        }
+    }
+
+    pub fn get_created(&self) -> &[TerraformVariableRegistrationResult] {
+        &self.created
+    }
+
+    pub fn get_updated(&self) -> &[PlannedVariable] {
+        &self.updated
+    }
+
+    pub fn get_unchanged(&self) -> &[String] {
+        &self.unchanged
+    }
+
+    pub fn get_ignored(&self) -> &[String] {
+        &self.ignored
+    }
+
+    pub fn get_not_owned(&self) -> &[String] {
+        &self.not_owned
+    }
+
+    pub fn get_pruned(&self) -> &[String] {
+        &self.pruned
+    }
+}
+
+/// Plan the changes of the workspaces, processing up to `concurrency` (at least one)
+/// workspaces at a time.
+///
+/// Plans are returned with their workspace in the order of `workspaces`. Nothing is written.
+pub async fn plan_workspaces<'a>(
+    client: &TerraformClient,
+    workspaces: &'a [TargetWorkspace],
+    export_values: &[ExportValue],
+    options: &ExportOptions,
+    concurrency: usize,
+) -> Vec<(&'a TargetWorkspace, Result<TargetPlan, TfvarExportError>)> {
+    futures::stream::iter(workspaces.iter())
+        .map(|workspace| async move {
+            let plan = plan_target(
+                client,
+                &VariableTarget::Workspace(workspace.get_workspace_id().to_owned()),
+                export_values,
+                options,
+            )
+            .await;
+            (workspace, plan)
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// Apply the plans of the workspaces, processing up to `concurrency` (at least one)
+/// workspaces at a time.
+///
+/// Results are returned with their workspace in the order of `plans` regardless of the completion
+/// order. A failure of a workspace does not stop the others.
+pub async fn apply_workspace_plans<'a>(
+    client: &TerraformClient,
+    plans: &'a [WorkspacePlan],
+    concurrency: usize,
+) -> Vec<(&'a TargetWorkspace, Result<ExportResult, TfvarExportError>)> {
+    futures::stream::iter(plans.iter())
+        .map(|plan| async move {
+            let result = apply_plan(client, plan.get_plan()).await;
+            (plan.get_workspace(), result)
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// Read the variables of the workspaces again and list the names of the variables changed since
+/// each plan was made.
+///
+/// The results are in the order of the plans. Up to `concurrency` (at least one) workspaces are read
+/// at a time.
+pub async fn changed_workspace_variables<'a>(
+    client: &TerraformClient,
+    plans: &'a [WorkspacePlan],
+    concurrency: usize,
+) -> Vec<(&'a TargetWorkspace, Result<Vec<String>, TfvarExportError>)> {
+    futures::stream::iter(plans.iter())
+        .map(|plan| async move {
+            let changed = client
+                .get_variables(plan.get_plan().get_target())
+                .await
+                .map(|existing_variables| plan.get_plan().changed_variables(&existing_variables));
+            (plan.get_workspace(), changed)
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path},
+        Mock,
+        MockServer,
+        Request,
+        Respond,
+        ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{
+        terraform_api::connection_prop::TerraformApiConnectionProperty,
+        utils::construct_export_value::construct_export_value,
+    };
+
+    /// Respond to a Create variable request with the requested attributes after the delay.
+    struct EchoVariable(std::time::Duration);
+
+    impl Respond for EchoVariable {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let mut body: serde_json::Value = request.body_json().unwrap();
+            body["data"]["id"] = json!("var-new");
+            ResponseTemplate::new(201)
+                .set_body_json(body)
+                .set_delay(self.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plan_apply_workspaces_order() {
+        const WORKSPACES: usize = 4;
+
+        let server = MockServer::start().await;
+        for i in 0..WORKSPACES {
+            // The first workspace responds last.
+            let delay = std::time::Duration::from_millis(((WORKSPACES - i) * 100) as u64);
+            Mock::given(method("GET"))
+                .and(path(format!("/api/v2/workspaces/ws-{}/vars", i)))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(json!({"data": []}))
+                        .set_delay(delay),
+                )
+                .mount(&server)
+                .await;
+            Mock::given(method("POST"))
+                .and(path(format!("/api/v2/workspaces/ws-{}/vars", i)))
+                .respond_with(EchoVariable(delay / 2))
+                .mount(&server)
+                .await;
+        }
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap();
+        let workspaces: Vec<TargetWorkspace> = (0..WORKSPACES)
+            .map(|i| TargetWorkspace::new(format!("workspace-{}", i), format!("ws-{}", i)))
+            .collect();
+        let export_values = construct_export_value(
+            "files/test/export_list_construct_export_value.txt",
+            "files/test/outputs.json",
+        )
+        .unwrap();
+
+        let start = std::time::Instant::now();
+        let plans: Vec<WorkspacePlan> = plan_workspaces(
+            &client,
+            &workspaces,
+            &export_values,
+            &ExportOptions::new("outputs.json"),
+            WORKSPACES,
+        )
+        .await
+        .into_iter()
+        .map(|(workspace, plan)| WorkspacePlan::new(workspace.to_owned(), plan.unwrap()))
+        .collect();
+        let res = apply_workspace_plans(&client, &plans, WORKSPACES).await;
+
+        // Processed in parallel
+        assert!(start.elapsed() < std::time::Duration::from_millis(1600));
+        assert_eq!(res.len(), WORKSPACES);
+        for (i, (workspace, workspace_result)) in res.iter().enumerate() {
+            // In the order of the workspaces
+            assert_eq!(*workspace, workspaces.get(i).unwrap());
+            let workspace_result = workspace_result.as_ref().unwrap();
+            assert_eq!(workspace_result.get_created().len(), export_values.len());
+            assert!(workspace_result.get_updated().is_empty());
+            assert!(workspace_result.get_ignored().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plan_workspaces_zero_concurrency() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/workspaces/ws-0/vars"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"data": []})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap();
+        let workspaces = vec![TargetWorkspace::new(
+            String::from("workspace-0"),
+            String::from("ws-0"),
+        )];
+        let export_values = construct_export_value(
+            "files/test/export_list_construct_export_value.txt",
+            "files/test/outputs.json",
+        )
+        .unwrap();
+
+        // Processed one at a time instead of never
+        let res = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            plan_workspaces(
+                &client,
+                &workspaces,
+                &export_values,
+                &ExportOptions::new("outputs.json"),
+                0,
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.len(), 1);
+        assert!(res.first().unwrap().1.is_ok());
+    }
+}
@@ -1,3 +1,7 @@
+mod backup;
+mod error;
+mod export;
+mod plan;
 mod terraform_api;
 mod utils;
 
@@ -6,183 +10,2162 @@ use std::collections::HashMap;
 use log::warn;
 
 use crate::{
+    backup::{apply_restore, default_backup_dir, plan_restore, take_backup, Backup},
+    error::TfvarExportError,
+    export::{
+        apply_workspace_plans,
+        changed_workspace_variables,
+        plan_workspaces,
+        ExportOptions,
+        ExportResult,
+        TargetWorkspace,
+        WorkspaceSelector,
+    },
+    plan::{
+        apply_plan,
+        plan_target,
+        plan_variables,
+        ExportPlan,
+        PlannedVariable,
+        TargetPlan,
+        VarsetPlan,
+        WorkspacePlan,
+    },
     terraform_api::{
-        check_variable_status::check_variable_status,
+        client::TerraformClient,
         connection_prop::TerraformApiConnectionProperty,
-        get_workspaces::get_workspaces,
-        register_variable::{create_variable, update_variable, TerraformVariableProperty},
+        get_workspaces::TerraformWorkspace,
+        rate_limiter::RateLimiter,
+        register_variable::VariableTarget,
+        retry::RetryPolicy,
+    },
+    utils::{
+        construct_export_value::{construct_export_value, ExportValue},
+        name_pattern::NamePattern,
     },
-    utils::construct_export_value::construct_export_value,
 };
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
     // Clap: Read command-line options
-    let clap = utils::clap::new_clap_command();
-    let base_url = clap.get_one::<String>("base_url").unwrap();
-    let target_workspaces = clap.try_get_one::<String>("target_workspaces").unwrap();
-    let disable_log = clap.get_flag("disable_log");
-    let show_workspaces = clap.get_flag("show_workspaces");
-    let allow_update = clap.get_flag("allow_update");
-    let output_values_file = clap.try_get_one::<String>("output_values_file").unwrap();
-    let export_list = clap.try_get_one::<String>("export_list").unwrap();
+    let clap = utils::clap::new_clap_command().get_matches();
+    let matches = match clap.subcommand() {
+        Some((_, matches)) => matches,
+        None => &clap,
+    };
 
     // Log
     let mut builder = env_logger::Builder::new();
-    match disable_log {
+    match matches.get_flag("disable_log") {
         true => builder.filter_level(log::LevelFilter::Error),
         false => builder.filter_level(log::LevelFilter::Info),
     };
     builder.init();
 
-    let organization_name = match std::env::var("TFVE_ORGANIZATION_NAME") {
-        Ok(x) => x,
-        _ => {
-            // `TFVE_ORGANIZATION_NAME` must be set if `show_workspaces` is specified
-            if show_workspaces {
-                panic!(
-                    "Failed to read an environment variable `{}`.",
-                    "TFVE_ORGANIZATION_NAME"
-                );
-            }
-            String::new()
-        },
-    };
+    let token = std::env::var("TFVE_TOKEN").ok();
+    let organization_name = std::env::var("TFVE_ORGANIZATION_NAME").ok();
+    if let Err(err) = run(&clap, token, organization_name).await {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
 
-    let api_conn_prop = TerraformApiConnectionProperty::new(
-        url::Url::parse(&base_url).expect("Failed to parse `base_url`."),
-        std::env::var("TFVE_TOKEN").expect(&format!(
-            "Failed to read an environment variable `{}`.",
-            "TFVE_TOKEN"
-        )),
-    );
+/// Run with the command-line options and the values of `TFVE_TOKEN` and
+/// `TFVE_ORGANIZATION_NAME`.
+async fn run(
+    clap: &clap::ArgMatches,
+    token: Option<String>,
+    organization_name: Option<String>,
+) -> Result<(), TfvarExportError> {
+    match clap.subcommand() {
+        Some(("apply", matches)) => run_apply(matches, token).await,
+        Some(("restore", matches)) => run_restore(matches, token).await,
+        Some(("plan", matches)) => run_export(matches, token, organization_name, true).await,
+        _ => run_export(clap, token, organization_name, false).await,
+    }
+}
+
+/// Export values, or only plan the changes if `plan_only` or `--dry-run` is set.
+async fn run_export(
+    clap: &clap::ArgMatches,
+    token: Option<String>,
+    organization_name: Option<String>,
+    plan_only: bool,
+) -> Result<(), TfvarExportError> {
+    let target_workspaces = clap.try_get_one::<String>("target_workspaces").unwrap();
+    let target_projects = clap.try_get_one::<String>("target_projects").unwrap();
+    let target_tags = clap.try_get_one::<String>("target_tags").unwrap();
+    let target_patterns: Vec<&String> = clap
+        .try_get_many::<String>("target_pattern")
+        .unwrap()
+        .unwrap_or_default()
+        .collect();
+    let target_regexes: Vec<&String> = clap
+        .try_get_many::<String>("target_regex")
+        .unwrap()
+        .unwrap_or_default()
+        .collect();
+    let exclude_workspaces = clap.try_get_one::<String>("exclude_workspaces").unwrap();
+    let varset = clap.try_get_one::<String>("varset").unwrap();
+    let varset_workspaces = clap.try_get_one::<String>("varset_workspaces").unwrap();
+    let varset_projects = clap.try_get_one::<String>("varset_projects").unwrap();
+    let show_workspaces = clap.get_flag("show_workspaces");
+    let plan_only = plan_only || clap.get_flag("dry_run");
+    let auto_approve = clap.get_flag("auto_approve");
+    let backup_dir = clap.get_one::<String>("backup_dir").map(|val| val.as_str());
+    // Only `plan` has `--out`.
+    let plan_out = clap.try_get_one::<String>("out").ok().flatten();
+    let source = clap.try_get_one::<String>("source").unwrap();
+    let output_values_file = clap.try_get_one::<String>("output_values_file").unwrap();
+    let export_list = clap.try_get_one::<String>("export_list").unwrap();
+    let concurrency = *clap.get_one::<usize>("concurrency").unwrap();
+
+    // Workspaces are got one by one unless the organization must be listed to select them.
+    let list_workspaces = target_projects.is_some()
+        || target_tags.is_some()
+        || !target_patterns.is_empty()
+        || !target_regexes.is_empty()
+        || varset_workspaces.is_some();
+
+    // `TFVE_ORGANIZATION_NAME` must be set if the organization is listed or `varset` is specified
+    if organization_name.is_none() && (show_workspaces || list_workspaces || varset.is_some()) {
+        return Err(TfvarExportError::Config(String::from(
+            "Environment variable `TFVE_ORGANIZATION_NAME` is required for `--show-workspaces`, \
+             `--varset`, `--varset-workspaces` and the `--target-*` options other than \
+             `--target-workspaces`.",
+        )));
+    }
+    let organization_name_or_empty = organization_name.as_deref().unwrap_or_default();
+
+    let client = new_client(clap, token)?;
 
     if show_workspaces {
-        get_workspaces(true, &organization_name, &api_conn_prop).await?;
+        client
+            .get_workspaces(true, organization_name_or_empty)
+            .await?;
         return Ok(());
     }
 
+    if !plan_only {
+        require_approval(auto_approve, "export values")?;
+    }
+
     // Workspace(s)
-    let workspace_name_id: HashMap<String, String> =
-        get_workspaces(false, &organization_name, &api_conn_prop)
+    let terraform_workspaces = if list_workspaces {
+        client
+            .get_workspaces(false, organization_name_or_empty)
             .await?
-            .into_iter()
-            .map(|val| {
-                (
-                    val.get_workspace_name().to_string(),
-                    val.get_workspace_id().to_string(),
-                )
-            })
+    } else {
+        let mut result = Vec::new();
+        for val in split_names(target_workspaces) {
+            if let Some(workspace) = client
+                .get_workspace(organization_name.as_deref(), &val)
+                .await?
+            {
+                result.push(workspace);
+            }
+        }
+        result
+    };
+    let mut patterns = Vec::new();
+    for val in target_patterns {
+        patterns.push(NamePattern::from_glob(val)?);
+    }
+    for val in target_regexes {
+        patterns.push(NamePattern::from_regex(val)?);
+    }
+    let mut workspace_selector = WorkspaceSelector::new()
+        .with_workspace_names(split_names(target_workspaces))
+        .with_project_names(split_names(target_projects))
+        .with_patterns(patterns)
+        .with_excluded_names(split_names(exclude_workspaces));
+    if target_tags.is_some() {
+        workspace_selector = workspace_selector.with_tagged_workspace_ids(
+            client
+                .get_tagged_workspace_ids(organization_name_or_empty, &split_names(target_tags))
+                .await?,
+        );
+    }
+    let workspaces = workspace_selector.resolve(&terraform_workspaces);
+    if !workspace_selector.is_empty() {
+        let workspace_names: Vec<&str> = workspaces
+            .iter()
+            .map(|val| val.get_workspace_name())
             .collect();
-    let workspace_names: Vec<String> = target_workspaces
-        .unwrap()
-        .split(',')
-        .map(|val| val.to_string())
-        .collect();
-    let mut workspace_ids: Vec<String> = Vec::new();
-    workspace_names
-        .into_iter()
-        .for_each(|val| match workspace_name_id.get(&val) {
-            Some(v) => workspace_ids.push(v.to_string()),
-            None => log::warn!("{}: No such workspace, skipping.", val),
-        });
+        println!("Target workspace(s): {}", workspace_names.join(", "));
+    }
 
     // Variable name and its value
-    let var_name_val = construct_export_value(export_list.unwrap(), output_values_file.unwrap())?;
-    let var_name_val_des_map: HashMap<String, (Option<String>, serde_json::Value)> = var_name_val
-        .iter()
-        .map(|val| {
-            (
-                val.get_variable_name().to_owned(),
-                (
-                    val.get_variable_description().to_owned(),
-                    val.get_value().to_owned(),
-                ),
+    let export_values = construct_export_value(export_list.unwrap(), output_values_file.unwrap())?;
+    let export_options = ExportOptions::new(source.unwrap())
+        .with_allow_update(clap.get_flag("allow_update"))
+        .with_adopt(clap.get_flag("adopt"))
+        .with_prune(clap.get_flag("prune"));
+
+    let color = utils::diff::use_color();
+
+    let varset_plan = match varset {
+        Some(varset_name) => {
+            let (workspace_ids, project_ids) = varset_target_ids(
+                &client,
+                organization_name_or_empty,
+                &terraform_workspaces,
+                split_names(varset_workspaces),
+                split_names(varset_projects),
             )
+            .await?;
+            let plan = plan_varset(
+                &client,
+                organization_name_or_empty,
+                varset_name,
+                workspace_ids,
+                project_ids,
+                &export_values,
+                &export_options,
+            )
+            .await?;
+            Some(plan)
+        },
+        None => None,
+    };
+    let (plan, mut failed_workspaces) = plan_export(
+        &client,
+        varset_plan,
+        &workspaces,
+        &export_values,
+        &export_options,
+        concurrency,
+        color,
+    )
+    .await;
+
+    if plan_only {
+        if !failed_workspaces.is_empty() {
+            return Err(TfvarExportError::WorkspacesFailed(failed_workspaces));
+        }
+        if let Some(path) = plan_out {
+            plan.save(path)?;
+            println!("Plan saved to `{}`.", path);
+        }
+        return Ok(());
+    }
+
+    // The workspaces failed to plan are reported, and the others are still exported.
+    if plan.has_changes() {
+        if !approve(auto_approve, "Do you want to export these values?")? {
+            println!("Export cancelled.");
+        } else {
+            match apply_export_plan(&client, &plan, backup_dir, concurrency, color).await {
+                Ok(()) => {},
+                Err(TfvarExportError::WorkspacesFailed(workspaces)) => {
+                    failed_workspaces.extend(workspaces)
+                },
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    if !failed_workspaces.is_empty() {
+        return Err(TfvarExportError::WorkspacesFailed(failed_workspaces));
+    }
+
+    Ok(())
+}
+
+/// Apply the plan saved in the plan file after checking that the variables have not changed since
+/// the plan was made.
+async fn run_apply(clap: &clap::ArgMatches, token: Option<String>) -> Result<(), TfvarExportError> {
+    let plan_file = clap.get_one::<String>("plan_file").unwrap();
+    let auto_approve = clap.get_flag("auto_approve");
+    let backup_dir = clap.get_one::<String>("backup_dir").map(|val| val.as_str());
+    let concurrency = *clap.get_one::<usize>("concurrency").unwrap();
+
+    require_approval(auto_approve, "apply a plan")?;
+    let plan = ExportPlan::load(plan_file)?;
+    let client = new_client(clap, token)?;
+
+    check_export_plan(&client, &plan, concurrency).await?;
+
+    let color = utils::diff::use_color();
+    if let Some(varset) = plan.get_varset() {
+        print_varset_plan(varset, color);
+    }
+    for val in plan.get_workspaces() {
+        println!(
+            "Workspace: {} ({})",
+            val.get_workspace().get_workspace_name(),
+            val.get_workspace().get_workspace_id()
+        );
+        print_plan(val.get_plan(), color);
+    }
+    if !plan.has_changes() {
+        return Ok(());
+    }
+    if !approve(auto_approve, "Do you want to apply this plan?")? {
+        println!("Apply cancelled.");
+        return Ok(());
+    }
+
+    apply_export_plan(&client, &plan, backup_dir, concurrency, color).await
+}
+
+/// Restore the variables of the workspaces and the variable set in the backup file.
+async fn run_restore(
+    clap: &clap::ArgMatches,
+    token: Option<String>,
+) -> Result<(), TfvarExportError> {
+    let backup_file = clap.get_one::<String>("backup_file").unwrap();
+    let auto_approve = clap.get_flag("auto_approve");
+
+    require_approval(auto_approve, "restore variables")?;
+    let backup = Backup::load(backup_file)?;
+    let client = new_client(clap, token)?;
+
+    let mut plans = Vec::new();
+    let mut failed_targets = Vec::new();
+    for val in backup.get_targets() {
+        match val.get_target() {
+            VariableTarget::Workspace(id) => println!("Workspace: {} ({})", val.get_name(), id),
+            VariableTarget::VariableSet(id) => {
+                println!("Variable set: {} ({})", val.get_name(), id)
+            },
+        }
+        match plan_restore(&client, val, backup.get_sources()).await {
+            Ok(plan) => {
+                if !plan.get_created().is_empty() {
+                    println!("Variable(s) to be created: {:#?}", plan.get_created());
+                }
+                if !plan.get_updated().is_empty() {
+                    println!("Variable(s) to be updated: {:#?}", plan.get_updated());
+                }
+                if !plan.get_deleted().is_empty() {
+                    println!("Variable(s) to be deleted: {:#?}", plan.get_deleted());
+                }
+                if !plan.get_unchanged().is_empty() {
+                    println!("Variable(s) unchanged: {:#?}", plan.get_unchanged());
+                }
+                if !plan.get_not_restorable().is_empty() {
+                    println!(
+                        "Sensitive variable(s) not restorable and left as they are: {:#?}",
+                        plan.get_not_restorable()
+                    );
+                }
+                plans.push((val.get_name(), plan));
+            },
+            Err(err) => {
+                log::error!("{}: {}", val.get_name(), err);
+                failed_targets.push(val.get_name().to_string());
+            },
+        }
+    }
+
+    plans.retain(|(_, plan)| plan.has_changes());
+    if !plans.is_empty() {
+        if approve(auto_approve, "Do you want to restore these variables?")? {
+            for (name, plan) in plans {
+                match apply_restore(&client, &plan).await {
+                    Ok(()) if plan.get_not_restorable().is_empty() => {
+                        println!("{}: Variable(s) restored.", name)
+                    },
+                    Ok(()) => println!(
+                        "{}: Variable(s) restored except sensitive ones: {}",
+                        name,
+                        plan.get_not_restorable().join(", ")
+                    ),
+                    Err(err) => {
+                        log::error!("{}: {}", name, err);
+                        failed_targets.push(name.to_string());
+                    },
+                }
+            }
+        } else {
+            println!("Restore cancelled.");
+        }
+    }
+
+    if !failed_targets.is_empty() {
+        return Err(TfvarExportError::RestoreFailed(failed_targets));
+    }
+
+    Ok(())
+}
+
+/// Refuse to `action` without `--auto-approve` if the confirmation cannot be asked, which requires
+/// a terminal.
+fn require_approval(auto_approve: bool, action: &str) -> Result<(), TfvarExportError> {
+    if !auto_approve && !utils::confirm::is_interactive() {
+        return Err(TfvarExportError::Config(format!(
+            "`--auto-approve` is required to {} when stdin is not a terminal.",
+            action
+        )));
+    }
+
+    Ok(())
+}
+
+/// Whether the changes shown above are approved; asked with `question` unless `--auto-approve`.
+fn approve(auto_approve: bool, question: &str) -> Result<bool, TfvarExportError> {
+    if auto_approve {
+        return Ok(true);
+    }
+
+    utils::confirm::ask_approval(
+        question,
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout(),
+    )
+    .map_err(|source| TfvarExportError::Io {
+        path: String::from("<stdin>"),
+        source,
+    })
+}
+
+/// Build the client of Terraform API from the command-line options and `TFVE_TOKEN`.
+fn new_client(
+    clap: &clap::ArgMatches,
+    token: Option<String>,
+) -> Result<TerraformClient, TfvarExportError> {
+    let base_url = clap.get_one::<String>("base_url").unwrap();
+    let connect_timeout = *clap.get_one::<u64>("connect_timeout").unwrap();
+    let request_timeout = *clap.get_one::<u64>("request_timeout").unwrap();
+    let max_attempts = *clap.get_one::<u32>("max_attempts").unwrap();
+    let rate_limit = *clap.get_one::<u64>("rate_limit").unwrap();
+
+    let api_conn_prop = TerraformApiConnectionProperty::new(
+        url::Url::parse(base_url).map_err(|err| {
+            TfvarExportError::Config(format!("Invalid `--base-url` `{}`: {}", base_url, err))
+        })?,
+        token.ok_or_else(|| {
+            TfvarExportError::Config(String::from(
+                "Environment variable `TFVE_TOKEN` is required.",
+            ))
+        })?,
+    )
+    .with_connect_timeout(std::time::Duration::from_secs(connect_timeout))
+    .with_request_timeout(std::time::Duration::from_secs(request_timeout));
+
+    Ok(TerraformClient::new(&api_conn_prop)?
+        .with_retry_policy(RetryPolicy::new(max_attempts))
+        .with_rate_limiter(RateLimiter::new(rate_limit)?))
+}
+
+/// IDs of the workspaces and the projects named to apply the variable set to.
+///
+/// Workspaces are looked up in `terraform_workspaces` by name or ID; unknown names are skipped.
+async fn varset_target_ids(
+    client: &TerraformClient,
+    organization_name: &str,
+    terraform_workspaces: &[TerraformWorkspace],
+    workspace_names: Vec<String>,
+    project_names: Vec<String>,
+) -> Result<(Vec<String>, Vec<String>), TfvarExportError> {
+    let workspace_ids: Vec<String> = workspace_names
+        .into_iter()
+        .filter_map(|val| {
+            match terraform_workspaces
+                .iter()
+                .find(|v| v.get_workspace_name() == val || v.get_workspace_id() == val)
+            {
+                Some(v) => Some(v.get_workspace_id().to_string()),
+                None => {
+                    log::warn!("{}: No such workspace, skipping.", val);
+                    None
+                },
+            }
         })
         .collect();
-    let target_variables = var_name_val
-        .iter()
-        .map(|val| val.get_variable_name().to_owned())
+
+    if project_names.is_empty() {
+        return Ok((workspace_ids, Vec::new()));
+    }
+    let project_name_id: HashMap<String, String> = client
+        .get_projects(organization_name)
+        .await?
+        .into_iter()
+        .map(|(id, name)| (name, id))
+        .collect();
+    let project_ids: Vec<String> = project_names
+        .into_iter()
+        .filter_map(|val| match project_name_id.get(&val) {
+            Some(v) => Some(v.to_string()),
+            None => {
+                log::warn!("{}: No such project, skipping.", val);
+                None
+            },
+        })
         .collect();
 
-    // Loop over workspace(s)
-    for workspace_id in workspace_ids {
-        // Variable status; existing or not
-        let status =
-            check_variable_status(&workspace_id, &api_conn_prop, &target_variables).await?;
-        // Variable(s) to be created
-        let vars_new: Vec<TerraformVariableProperty> = status
+    Ok((workspace_ids, project_ids))
+}
+
+/// Plan the variables of the variable set, which is to be created if it does not exist, and
+/// applying it to the workspaces and the projects it is not applied to yet.
+async fn plan_varset(
+    client: &TerraformClient,
+    organization_name: &str,
+    varset_name: &str,
+    workspace_ids: Vec<String>,
+    project_ids: Vec<String>,
+    export_values: &[ExportValue],
+    export_options: &ExportOptions,
+) -> Result<VarsetPlan, TfvarExportError> {
+    let varset = client.get_varset(organization_name, varset_name).await?;
+    let (plan, applied_ids) = match &varset {
+        Some(val) => {
+            let target = VariableTarget::VariableSet(val.get_id().to_owned());
+            let plan = plan_target(client, &target, export_values, export_options).await?;
+            let mut applied_ids = val.get_related_ids("workspaces");
+            applied_ids.extend(val.get_related_ids("projects"));
+            (plan, applied_ids)
+        },
+        None => {
+            let target = VariableTarget::VariableSet(String::new());
+            let plan = plan_variables(&target, &[], export_values, export_options);
+            (plan, Vec::new())
+        },
+    };
+    let not_applied = |ids: Vec<String>| -> Vec<String> {
+        ids.into_iter()
+            .filter(|id| !applied_ids.contains(&id.as_str()))
+            .collect()
+    };
+
+    Ok(VarsetPlan::new(
+        organization_name.to_string(),
+        varset_name.to_string(),
+        varset.as_ref().map(|val| val.get_id().to_string()),
+        plan,
+    )
+    .with_workspace_ids(not_applied(workspace_ids))
+    .with_project_ids(not_applied(project_ids)))
+}
+
+/// Plan the changes of the workspaces and print them with the plan of the variable set without
+/// writing anything.
+///
+/// The workspaces failed to plan are reported and returned along with the plan of the others.
+async fn plan_export(
+    client: &TerraformClient,
+    varset_plan: Option<VarsetPlan>,
+    workspaces: &[TargetWorkspace],
+    export_values: &[ExportValue],
+    export_options: &ExportOptions,
+    concurrency: usize,
+    color: bool,
+) -> (ExportPlan, Vec<String>) {
+    if let Some(varset) = &varset_plan {
+        print_varset_plan(varset, color);
+    }
+
+    let plans = plan_workspaces(
+        client,
+        workspaces,
+        export_values,
+        export_options,
+        concurrency,
+    )
+    .await;
+
+    let mut workspace_plans = Vec::new();
+    let mut failed_workspaces = Vec::new();
+    for (workspace, plan) in plans {
+        println!(
+            "Workspace: {} ({})",
+            workspace.get_workspace_name(),
+            workspace.get_workspace_id()
+        );
+        match plan {
+            Ok(plan) => {
+                print_plan(&plan, color);
+                workspace_plans.push(WorkspacePlan::new(workspace.to_owned(), plan));
+            },
+            Err(err) => {
+                log::error!("{}: {}", workspace.get_workspace_name(), err);
+                failed_workspaces.push(workspace.get_workspace_name().to_string());
+            },
+        }
+    }
+
+    (
+        ExportPlan::new(varset_plan, workspace_plans),
+        failed_workspaces,
+    )
+}
+
+/// Fail with the targets whose variables have changed since the plan was made.
+async fn check_export_plan(
+    client: &TerraformClient,
+    plan: &ExportPlan,
+    concurrency: usize,
+) -> Result<(), TfvarExportError> {
+    let mut changed_targets = Vec::new();
+
+    if let Some(varset) = plan.get_varset() {
+        let varset_name = varset.get_varset_name();
+        match varset.get_varset_id() {
+            Some(varset_id) => {
+                let target = VariableTarget::VariableSet(varset_id.to_owned());
+                let existing_variables = client.get_variables(&target).await?;
+                let changed = varset
+                    .target_plan(varset_id)
+                    .changed_variables(&existing_variables);
+                if !changed.is_empty() {
+                    changed_targets.push(format!(
+                        "variable set {}: {}",
+                        varset_name,
+                        changed.join(", ")
+                    ));
+                }
+            },
+            None => {
+                if client
+                    .get_varset_id(varset.get_organization_name(), varset_name)
+                    .await?
+                    .is_some()
+                {
+                    changed_targets.push(format!("variable set {}: created", varset_name));
+                }
+            },
+        }
+    }
+
+    for (workspace, changed) in
+        changed_workspace_variables(client, plan.get_workspaces(), concurrency).await
+    {
+        let changed = changed?;
+        if !changed.is_empty() {
+            changed_targets.push(format!(
+                "workspace {}: {}",
+                workspace.get_workspace_name(),
+                changed.join(", ")
+            ));
+        }
+    }
+
+    if !changed_targets.is_empty() {
+        return Err(TfvarExportError::StalePlan(changed_targets));
+    }
+
+    Ok(())
+}
+
+/// Apply the plan to the variable set, creating it if planned so, and then to the workspaces.
+///
+/// The variables of the targets to be changed are backed up to `backup_dir`, or the default
+/// directory if `None`, first.
+async fn apply_export_plan(
+    client: &TerraformClient,
+    plan: &ExportPlan,
+    backup_dir: Option<&str>,
+    concurrency: usize,
+    color: bool,
+) -> Result<(), TfvarExportError> {
+    let changed_targets = plan.changed_targets();
+    if !changed_targets.is_empty() {
+        let backup_dir = match backup_dir {
+            Some(val) => val.to_string(),
+            None => default_backup_dir()?,
+        };
+        let backup = take_backup(client, &changed_targets, plan.sources(), concurrency).await?;
+        let path = backup.save(&backup_dir)?;
+        println!("Backup saved to `{}`.", path);
+    }
+
+    if let Some(varset) = plan.get_varset() {
+        let varset_name = varset.get_varset_name();
+        let varset_id = match varset.get_varset_id() {
+            Some(val) => val.to_owned(),
+            None => {
+                client
+                    .create_varset(varset.get_organization_name(), varset_name)
+                    .await?
+            },
+        };
+        let result = apply_plan(client, &varset.target_plan(&varset_id)).await?;
+        println!("Variable set: {} ({})", varset_name, varset_id);
+        print_export_result(varset_name, &result, color);
+
+        client
+            .apply_varset_to_workspaces(&varset_id, varset.get_workspace_ids())
+            .await?;
+        client
+            .apply_varset_to_projects(&varset_id, varset.get_project_ids())
+            .await?;
+    }
+
+    let results = apply_workspace_plans(client, plan.get_workspaces(), concurrency).await;
+
+    print_workspace_results(results, color)
+}
+
+/// Print the results in the order of the target workspaces and fail with the failed ones.
+fn print_workspace_results(
+    results: Vec<(&TargetWorkspace, Result<ExportResult, TfvarExportError>)>,
+    color: bool,
+) -> Result<(), TfvarExportError> {
+    let mut failed_workspaces = Vec::new();
+    for (workspace, result) in results {
+        println!(
+            "Workspace: {} ({})",
+            workspace.get_workspace_name(),
+            workspace.get_workspace_id()
+        );
+        match result {
+            Ok(result) => print_export_result(workspace.get_workspace_name(), &result, color),
+            Err(err) => {
+                log::error!("{}: {}", workspace.get_workspace_name(), err);
+                failed_workspaces.push(workspace.get_workspace_name().to_string());
+            },
+        }
+    }
+
+    if !failed_workspaces.is_empty() {
+        return Err(TfvarExportError::WorkspacesFailed(failed_workspaces));
+    }
+
+    Ok(())
+}
+
+/// Print the plan of the variable set including the workspaces and projects to apply it to.
+fn print_varset_plan(varset: &VarsetPlan, color: bool) {
+    let varset_id = match varset.get_varset_id() {
+        Some(val) => {
+            println!("Variable set: {} ({})", varset.get_varset_name(), val);
+            val.as_str()
+        },
+        None => {
+            println!("Variable set: {} (to be created)", varset.get_varset_name());
+            ""
+        },
+    };
+    print_plan(&varset.target_plan(varset_id), color);
+    if !varset.get_workspace_ids().is_empty() {
+        println!(
+            "  Applied to workspace(s): {}",
+            varset.get_workspace_ids().join(", ")
+        );
+    }
+    if !varset.get_project_ids().is_empty() {
+        println!(
+            "  Applied to project(s): {}",
+            varset.get_project_ids().join(", ")
+        );
+    }
+}
+
+/// Print the planned change of each variable.
+fn print_plan(plan: &TargetPlan, color: bool) {
+    for val in plan.get_variables() {
+        print_planned_variable(val, color);
+    }
+    if !plan.has_changes() {
+        println!("  No changes.");
+    }
+}
+
+/// Print the change of a variable followed by the diff of its value if updated.
+fn print_planned_variable(variable: &PlannedVariable, color: bool) {
+    println!("  {}", variable);
+    for line in variable.render_diff(color) {
+        println!("      {}", line);
+    }
+}
+
+/// Split comma separated names; `None` is no names.
+fn split_names(names: Option<&String>) -> Vec<String> {
+    match names {
+        Some(names) => names.split(',').map(|val| val.to_string()).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Print the result of exporting values to the workspace or the variable set `name`.
+fn print_export_result(name: &str, result: &ExportResult, color: bool) {
+    if !result.get_created().is_empty() {
+        println!("Variable(s) created: {:#?}", result.get_created());
+    }
+    if !result.get_updated().is_empty() {
+        println!("Variable(s) updated:");
+        for val in result.get_updated() {
+            print_planned_variable(val, color);
+        }
+    }
+    if !result.get_unchanged().is_empty() {
+        println!("Variable(s) unchanged: {:#?}", result.get_unchanged());
+    }
+    if !result.get_ignored().is_empty() {
+        warn!(
+            "{}: Following variable(s) were ignored because they are existing but \
+             `--allow_update` is not specified: {:#?}",
+            name,
+            result.get_ignored()
+        );
+    }
+    if !result.get_not_owned().is_empty() {
+        warn!(
+            "{}: Following variable(s) were not updated because they were not created by this \
+             tool from the same source and `--adopt` is not specified: {:#?}",
+            name,
+            result.get_not_owned()
+        );
+    }
+    if !result.get_pruned().is_empty() {
+        println!("Variable(s) deleted: {:#?}", result.get_pruned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        backup::TargetBackup,
+        terraform_api::{
+            mock_server::{MockTerraformServer, MOCK_TOKEN},
+            models::VariableCategory,
+            register_variable::VariableTarget,
+        },
+    };
+
+    const ORGANIZATION_NAME: &str = "org";
+    /// Ownership marker of the variables exported from the test output values file
+    const MARKER: &str = "[managed by tfvar-export from outputs.json]";
+
+    /// Directory of the backups taken by the tests which do not check them
+    fn test_backup_dir() -> String {
+        std::env::temp_dir()
+            .join("tfvar-export-test")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Run against the mock server with the test files and `args`.
+    async fn run_mock(server: &MockTerraformServer, args: &[&str]) -> Result<(), TfvarExportError> {
+        run_mock_with_export_list(
+            server,
+            args,
+            "files/test/export_list_construct_export_value.txt",
+        )
+        .await
+    }
+
+    async fn run_mock_with_export_list(
+        server: &MockTerraformServer,
+        args: &[&str],
+        export_list: &str,
+    ) -> Result<(), TfvarExportError> {
+        let base_url = server.uri();
+        let mut argv = vec![
+            "tfvar-export",
+            "--base-url",
+            &base_url,
+            "--rate-limit",
+            "1000",
+            "--auto-approve",
+        ];
+        let backup_dir = test_backup_dir();
+        if !args.contains(&"--backup-dir") {
+            argv.extend_from_slice(&["--backup-dir", &backup_dir]);
+        }
+        // Required for every run
+        if !args.contains(&"--source") {
+            argv.extend_from_slice(&["--source", "outputs.json"]);
+        }
+        argv.extend_from_slice(args);
+        argv.extend_from_slice(&["files/test/outputs.json", export_list]);
+        let clap = utils::clap::new_clap_command()
+            .try_get_matches_from(argv)
+            .unwrap();
+
+        run(
+            &clap,
+            Some(MOCK_TOKEN.to_string()),
+            Some(ORGANIZATION_NAME.to_string()),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_run_create_update_skip() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let project_id = server.add_project(ORGANIZATION_NAME, "project");
+        let ws_new = server.add_workspace(ORGANIZATION_NAME, "ws-new", Some(&project_id));
+        let ws_existing = server.add_workspace(ORGANIZATION_NAME, "ws-existing", None);
+        server.add_variable(
+            &ws_existing,
+            json!({"key": "string_out", "value": "old", "description": "old description"}),
+        );
+
+        // Existing variables are skipped without `--allow-update`; unknown workspaces are skipped.
+        run_mock(&server, &["-t", "ws-new,ws-existing,ws-unknown"])
+            .await
+            .unwrap();
+        assert_eq!(server.variable_keys(&ws_new), vec![
+            "number_0_out",
+            "set_of_object_out",
+            "string_out"
+        ]);
+        assert_eq!(
+            server
+                .variable(&ws_new, "number_0_out", VariableCategory::Terraform)
+                .unwrap(),
+            json!({
+                "key": "number_0_out",
+                "value": "0",
+                "description": MARKER,
+                "category": "terraform",
+                "hcl": false,
+                "sensitive": false
+            })
+        );
+        assert_eq!(
+            server
+                .variable(&ws_new, "set_of_object_out", VariableCategory::Terraform)
+                .unwrap()["hcl"],
+            json!(true)
+        );
+        assert_eq!(server.variable_keys(&ws_existing), vec![
+            "number_0_out",
+            "set_of_object_out",
+            "string_out"
+        ]);
+        assert_eq!(
+            server
+                .variable(&ws_existing, "string_out", VariableCategory::Terraform)
+                .unwrap()["value"],
+            json!("old")
+        );
+
+        // Variables not created by this tool are not updated even with `--allow-update`.
+        run_mock(&server, &["-t", "ws-existing", "--allow-update"])
+            .await
+            .unwrap();
+        let variable = server
+            .variable(&ws_existing, "string_out", VariableCategory::Terraform)
+            .unwrap();
+        assert_eq!(variable["value"], json!("old"));
+        assert_eq!(variable["description"], json!("old description"));
+
+        // Nor variables created from another source
+        run_mock(&server, &[
+            "-t",
+            "ws-existing",
+            "--allow-update",
+            "--adopt",
+            "--source",
+            "other",
+        ])
+        .await
+        .unwrap();
+        run_mock(&server, &["-t", "ws-existing", "--allow-update"])
+            .await
+            .unwrap();
+        let variable = server
+            .variable(&ws_existing, "number_0_out", VariableCategory::Terraform)
+            .unwrap();
+        assert_eq!(
+            variable["description"],
+            json!("[managed by tfvar-export from other]")
+        );
+
+        // Updated and taken over with `--adopt`
+        run_mock(&server, &["-t", "ws-existing", "--allow-update", "--adopt"])
+            .await
+            .unwrap();
+        let variable = server
+            .variable(&ws_existing, "string_out", VariableCategory::Terraform)
+            .unwrap();
+        assert_eq!(variable["value"], json!("aaa"));
+        assert_eq!(
+            variable["description"],
+            json!(format!("string_description {}", MARKER))
+        );
+        assert_eq!(
+            server
+                .variable(&ws_existing, "number_0_out", VariableCategory::Terraform)
+                .unwrap()["description"],
+            json!(MARKER)
+        );
+        assert_eq!(server.variable_keys(&ws_existing).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_unchanged() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let workspace_id = server.add_workspace(ORGANIZATION_NAME, "ws", None);
+        // Same value in another format
+        server.add_variable(
+            &workspace_id,
+            json!({
+                "key": "set_of_object_out",
+                "value": "[\n  {\n    type = \"bbb\"\n    name = \"aaa\"\n  },\n]",
+                "description": format!("set_of_object_description {}", MARKER),
+                "hcl": true
+            }),
+        );
+        server.add_variable(
+            &workspace_id,
+            json!({"key": "number_0_out", "value": "1", "description": MARKER}),
+        );
+        let patch_count = || async {
+            server
+                .received_requests()
+                .await
+                .iter()
+                .filter(|val| val.starts_with("PATCH "))
+                .count()
+        };
+
+        // Only the changed variable is updated.
+        run_mock(&server, &["-t", "ws", "--allow-update"])
+            .await
+            .unwrap();
+        assert_eq!(patch_count().await, 1);
+        assert_eq!(
+            server
+                .variable(&workspace_id, "number_0_out", VariableCategory::Terraform)
+                .unwrap()["value"],
+            json!("0")
+        );
+        assert_eq!(server.variable_keys(&workspace_id).len(), 3);
+
+        // Nothing is updated once up to date.
+        run_mock(&server, &["-t", "ws", "--allow-update"])
+            .await
+            .unwrap();
+        assert_eq!(patch_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_dry_run() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let workspace_id = server.add_workspace(ORGANIZATION_NAME, "ws", None);
+        server.add_variable(
+            &workspace_id,
+            json!({"key": "number_0_out", "value": "1", "description": MARKER}),
+        );
+        server.add_variable(
+            &workspace_id,
+            json!({"key": "stale", "description": MARKER}),
+        );
+
+        run_mock(&server, &[
+            "-t",
+            "ws",
+            "--varset",
+            "shared",
+            "--allow-update",
+            "--prune",
+            "--dry-run",
+        ])
+        .await
+        .unwrap();
+
+        // Nothing is written.
+        assert!(server
+            .received_requests()
+            .await
             .iter()
-            .filter(|val| val.get_variable_id().is_none())
-            .map(|val| {
-                TerraformVariableProperty::new(
-                    None,
-                    val.get_variable_name().to_owned(),
-                    var_name_val_des_map
-                        .get(val.get_variable_name())
-                        .unwrap()
-                        .0
-                        .to_owned(),
-                    var_name_val_des_map
-                        .get(val.get_variable_name())
-                        .unwrap()
-                        .1
-                        .to_owned(),
+            .all(|val| val.starts_with("GET ")));
+        assert_eq!(server.variable_keys(&workspace_id), vec![
+            "number_0_out",
+            "stale"
+        ]);
+        assert_eq!(server.variable_set_id(ORGANIZATION_NAME, "shared"), None);
+    }
+
+    #[tokio::test]
+    async fn test_run_plan_apply() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let workspace_id = server.add_workspace(ORGANIZATION_NAME, "ws", None);
+        server.add_variable(
+            &workspace_id,
+            json!({"key": "number_0_out", "value": "1", "description": MARKER}),
+        );
+        let plan_file = std::env::temp_dir().join(format!("plan-{}.json", uuid::Uuid::new_v4()));
+        let plan_file = plan_file.to_str().unwrap();
+        let base_url = server.uri();
+        let plan = |target: &str| {
+            utils::clap::new_clap_command()
+                .try_get_matches_from([
+                    "tfvar-export",
+                    "plan",
+                    "--base-url",
+                    &base_url,
+                    "-t",
+                    target,
+                    "--varset",
+                    "shared",
+                    "--varset-workspaces",
+                    "ws",
+                    "-u",
+                    "--source",
+                    "outputs.json",
+                    "--out",
+                    plan_file,
+                    "files/test/outputs.json",
+                    "files/test/export_list_construct_export_value.txt",
+                ])
+                .unwrap()
+        };
+        let apply = utils::clap::new_clap_command()
+            .try_get_matches_from([
+                "tfvar-export",
+                "apply",
+                "--base-url",
+                &base_url,
+                "--auto-approve",
+                "--backup-dir",
+                &test_backup_dir(),
+                plan_file,
+            ])
+            .unwrap();
+
+        // Nothing is written by `plan`.
+        run(
+            &plan("ws"),
+            Some(MOCK_TOKEN.to_string()),
+            Some(ORGANIZATION_NAME.to_string()),
+        )
+        .await
+        .unwrap();
+        assert!(server
+            .received_requests()
+            .await
+            .iter()
+            .all(|val| val.starts_with("GET ")));
+        assert_eq!(server.variable_set_id(ORGANIZATION_NAME, "shared"), None);
+
+        // `apply` requires only the plan file.
+        run(&apply, Some(MOCK_TOKEN.to_string()), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            server
+                .variable(&workspace_id, "number_0_out", VariableCategory::Terraform)
+                .unwrap()["value"],
+            json!("0")
+        );
+        assert_eq!(server.variable_keys(&workspace_id).len(), 3);
+        let varset_id = server.variable_set_id(ORGANIZATION_NAME, "shared").unwrap();
+        assert_eq!(server.variable_keys(&varset_id).len(), 3);
+        assert_eq!(server.variable_set_workspaces(&varset_id), vec![
+            workspace_id.clone()
+        ]);
+
+        // Refused once the variables have changed since the plan was made.
+        let ws_changed = server.add_workspace(ORGANIZATION_NAME, "ws-changed", None);
+        run(
+            &plan("ws-changed"),
+            Some(MOCK_TOKEN.to_string()),
+            Some(ORGANIZATION_NAME.to_string()),
+        )
+        .await
+        .unwrap();
+        server.add_variable(
+            &ws_changed,
+            json!({"key": "string_out", "value": "by hand"}),
+        );
+        let requests = server.received_requests().await.len();
+        match run(&apply, Some(MOCK_TOKEN.to_string()), None).await {
+            Err(TfvarExportError::StalePlan(targets)) => {
+                assert_eq!(targets, vec!["workspace ws-changed: string_out"])
+            },
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        assert!(server.received_requests().await[requests..]
+            .iter()
+            .all(|val| val.starts_with("GET ")));
+        assert_eq!(server.variable_keys(&ws_changed), vec!["string_out"]);
+
+        // Refused once only the value of a sensitive variable has changed, which is not returned.
+        let ws_sensitive = server.add_workspace(ORGANIZATION_NAME, "ws-sensitive", None);
+        server.add_variable(
+            &ws_sensitive,
+            json!({
+                "key": "string_out",
+                "value": "aaa",
+                "description": format!("string_description {}", MARKER),
+                "sensitive": true
+            }),
+        );
+        run(
+            &plan("ws-sensitive"),
+            Some(MOCK_TOKEN.to_string()),
+            Some(ORGANIZATION_NAME.to_string()),
+        )
+        .await
+        .unwrap();
+        server.set_variable_value(
+            &ws_sensitive,
+            "string_out",
+            VariableCategory::Terraform,
+            "by hand",
+        );
+        match run(&apply, Some(MOCK_TOKEN.to_string()), None).await {
+            Err(TfvarExportError::StalePlan(targets)) => {
+                assert_eq!(targets, vec!["workspace ws-sensitive: string_out"])
+            },
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        assert_eq!(
+            server
+                .variable(&ws_sensitive, "string_out", VariableCategory::Terraform)
+                .unwrap()["value"],
+            json!("by hand")
+        );
+
+        // Unsupported version
+        std::fs::write(
+            plan_file,
+            r#"{"version": 0, "varset": null, "workspaces": []}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            run(&apply, Some(MOCK_TOKEN.to_string()), None).await,
+            Err(TfvarExportError::Validation(_))
+        ));
+        std::fs::remove_file(plan_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_backup_restore() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let workspace_id = server.add_workspace(ORGANIZATION_NAME, "ws", None);
+        server.add_variable(
+            &workspace_id,
+            json!({"key": "number_0_out", "value": "1", "description": MARKER}),
+        );
+        server.add_variable(
+            &workspace_id,
+            json!({
+                "key": "stale",
+                "value": "{ a = 1 }",
+                "description": format!("stale {}", MARKER),
+                "hcl": true
+            }),
+        );
+        server.add_variable(
+            &workspace_id,
+            json!({"key": "SECRET", "category": "env", "sensitive": true}),
+        );
+        let dir = std::env::temp_dir().join(format!("backup-{}", uuid::Uuid::new_v4()));
+        let dir = dir.to_str().unwrap();
+
+        // Backed up before exporting
+        run_mock(&server, &["-t", "ws", "-u", "--prune", "--backup-dir", dir])
+            .await
+            .unwrap();
+        assert_eq!(server.variable_keys(&workspace_id), vec![
+            "SECRET",
+            "number_0_out",
+            "set_of_object_out",
+            "string_out"
+        ]);
+        let backup_files: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|val| val.unwrap().path().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(backup_files.len(), 1);
+        let backup = Backup::load(backup_files.first().unwrap()).unwrap();
+        assert_eq!(backup.get_targets().len(), 1);
+        assert_eq!(backup.get_targets()[0].get_name(), "ws");
+        // Sensitive variables are not backed up but their keys are.
+        assert_eq!(backup.get_targets()[0].get_variables().len(), 2);
+        assert_eq!(backup.get_targets()[0].get_sensitive_variables().len(), 1);
+        assert_eq!(backup.get_sources(), ["outputs.json"]);
+
+        // Nothing is backed up without changes.
+        run_mock(&server, &["-t", "ws", "-u", "--backup-dir", dir])
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_dir(dir).unwrap().count(), 1);
+        server.add_variable(&workspace_id, json!({"key": "by_hand", "value": "1"}));
+        server.add_variable(
+            &workspace_id,
+            json!({
+                "key": "other_source",
+                "value": "1",
+                "description": "[managed by tfvar-export from other]"
+            }),
+        );
+
+        // Sensitive variables are reported as not restorable.
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            MOCK_TOKEN.to_string(),
+        ))
+        .unwrap();
+        let plan = plan_restore(&client, &backup.get_targets()[0], backup.get_sources())
+            .await
+            .unwrap();
+        assert_eq!(plan.get_not_restorable(), ["SECRET"]);
+        assert!(!plan.get_deleted().contains(&String::from("SECRET")));
+
+        // The deleted variable is created again, the updated one is restored and the created ones
+        // are deleted.
+        let clap = utils::clap::new_clap_command()
+            .try_get_matches_from([
+                "tfvar-export",
+                "restore",
+                "--base-url",
+                &server.uri(),
+                "--auto-approve",
+                backup_files.first().unwrap(),
+            ])
+            .unwrap();
+        run(&clap, Some(MOCK_TOKEN.to_string()), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            server
+                .variable(&workspace_id, "number_0_out", VariableCategory::Terraform)
+                .unwrap()["value"],
+            json!("1")
+        );
+        assert_eq!(
+            server
+                .variable(&workspace_id, "stale", VariableCategory::Terraform)
+                .unwrap(),
+            json!({
+                "key": "stale",
+                "value": "{ a = 1 }",
+                "description": format!("stale {}", MARKER),
+                "category": "terraform",
+                "hcl": true,
+                "sensitive": false
+            })
+        );
+        // Only the variables created since then from the source exported after the backup are
+        // deleted.
+        assert_eq!(server.variable_keys(&workspace_id), vec![
+            "SECRET",
+            "by_hand",
+            "number_0_out",
+            "other_source",
+            "stale"
+        ]);
+
+        // Targets failed to be restored are reported as such.
+        let backup_file = Backup::new(
+            vec![TargetBackup::new(
+                String::from("missing"),
+                VariableTarget::Workspace(String::from("ws-missing")),
+                &[],
+            )],
+            Vec::new(),
+        )
+        .save(dir)
+        .unwrap();
+        let clap = utils::clap::new_clap_command()
+            .try_get_matches_from([
+                "tfvar-export",
+                "restore",
+                "--base-url",
+                &server.uri(),
+                "--auto-approve",
+                &backup_file,
+            ])
+            .unwrap();
+        match run(&clap, Some(MOCK_TOKEN.to_string()), None).await {
+            Err(err @ TfvarExportError::RestoreFailed(_)) => {
+                assert_eq!(err.to_string(), "Failed to restore target(s): missing")
+            },
+            res => panic!("Restore should fail: {:?}", res),
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_restore_recreated() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let workspace_id = server.add_workspace(ORGANIZATION_NAME, "ws", None);
+        server.add_variable(
+            &workspace_id,
+            json!({"key": "string_out", "value": "old", "description": MARKER}),
+        );
+        let dir = std::env::temp_dir().join(format!("backup-{}", uuid::Uuid::new_v4()));
+        let export_list = dir.join("export_list.txt");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&export_list, "number_0,number_0_out\n").unwrap();
+        let backup_dir = dir.join("backups");
+        let backup_dir = backup_dir.to_str().unwrap();
+
+        // `string_out` is pruned after the backup,
+        run_mock_with_export_list(
+            &server,
+            &["-t", "ws", "-u", "--prune", "--backup-dir", backup_dir],
+            export_list.to_str().unwrap(),
+        )
+        .await
+        .unwrap();
+        let backup_files: Vec<String> = std::fs::read_dir(backup_dir)
+            .unwrap()
+            .map(|val| val.unwrap().path().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(server.variable_keys(&workspace_id), vec!["number_0_out"]);
+        // and created again with another ID.
+        run_mock(&server, &["-t", "ws", "-u", "--backup-dir", backup_dir])
+            .await
+            .unwrap();
+
+        let clap = utils::clap::new_clap_command()
+            .try_get_matches_from([
+                "tfvar-export",
+                "restore",
+                "--base-url",
+                &server.uri(),
+                "--auto-approve",
+                backup_files.first().unwrap(),
+            ])
+            .unwrap();
+        run(&clap, Some(MOCK_TOKEN.to_string()), None)
+            .await
+            .unwrap();
+        // The variable created again is restored instead of being deleted.
+        assert_eq!(server.variable_keys(&workspace_id), vec!["string_out"]);
+        assert_eq!(
+            server
+                .variable(&workspace_id, "string_out", VariableCategory::Terraform)
+                .unwrap()["value"],
+            json!("old")
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_source() {
+        // `--source` is always required, as a file name such as `outputs.json` is shared by stacks.
+        for flags in [&[][..], &["--dry-run"], &["--allow-update"], &["--prune"]] {
+            let mut argv = vec!["tfvar-export", "-t", "ws"];
+            argv.extend_from_slice(flags);
+            argv.extend_from_slice(&[
+                "files/test/outputs.json",
+                "files/test/export_list_construct_export_value.txt",
+            ]);
+            assert_eq!(
+                utils::clap::new_clap_command()
+                    .try_get_matches_from(argv)
+                    .unwrap_err()
+                    .kind(),
+                clap::error::ErrorKind::MissingRequiredArgument
+            );
+        }
+
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let workspace_id = server.add_workspace(ORGANIZATION_NAME, "ws", None);
+        let base_url = server.uri();
+        let backup_dir = test_backup_dir();
+
+        // Two stacks having their own `outputs.json`
+        let dir = std::env::temp_dir().join(format!("tfvar-export-test-{}", uuid::Uuid::new_v4()));
+        let stacks = ["network", "app"].map(|stack| {
+            std::fs::create_dir_all(dir.join(stack)).unwrap();
+            let output_values_file = dir.join(stack).join("outputs.json");
+            std::fs::copy("files/test/outputs.json", &output_values_file).unwrap();
+            output_values_file.to_string_lossy().to_string()
+        });
+        // Exports the value of another output to `string_out` of the network stack.
+        let export_list_app = dir.join("export_list_app.txt");
+        std::fs::write(&export_list_app, "number_0,string_out\nbool,bool_out\n").unwrap();
+        let export_list_app = export_list_app.to_string_lossy().to_string();
+
+        let run_stack = |source: &str, output_values_file: &str, export_list: &str| {
+            let clap = utils::clap::new_clap_command()
+                .try_get_matches_from([
+                    "tfvar-export",
+                    "--base-url",
+                    &base_url,
+                    "--auto-approve",
+                    "--backup-dir",
+                    &backup_dir,
+                    "-t",
+                    "ws",
+                    "--allow-update",
+                    "--prune",
+                    "--source",
+                    source,
+                    output_values_file,
+                    export_list,
+                ])
+                .unwrap();
+            async move {
+                run(
+                    &clap,
+                    Some(MOCK_TOKEN.to_string()),
+                    Some(ORGANIZATION_NAME.to_string()),
+                )
+                .await
+            }
+        };
+
+        run_stack(
+            "network",
+            &stacks[0],
+            "files/test/export_list_construct_export_value.txt",
+        )
+        .await
+        .unwrap();
+        run_stack("app", &stacks[1], &export_list_app)
+            .await
+            .unwrap();
+
+        // The variables of the network stack are neither pruned nor overwritten by the app stack.
+        assert_eq!(server.variable_keys(&workspace_id), vec![
+            "bool_out",
+            "number_0_out",
+            "set_of_object_out",
+            "string_out"
+        ]);
+        let variable = server
+            .variable(&workspace_id, "string_out", VariableCategory::Terraform)
+            .unwrap();
+        assert_eq!(variable["value"], json!("aaa"));
+        assert_eq!(
+            variable["description"],
+            json!("string_description [managed by tfvar-export from network]")
+        );
+        assert_eq!(
+            server
+                .variable(&workspace_id, "bool_out", VariableCategory::Terraform)
+                .unwrap()["description"],
+            json!("[managed by tfvar-export from app]")
+        );
+
+        // Each stack prunes only its own variables.
+        run_stack(
+            "network",
+            &stacks[0],
+            "files/test/export_list.with_options.txt",
+        )
+        .await
+        .unwrap();
+        assert_eq!(server.variable_keys(&workspace_id), vec![
+            "SECRET",
+            "STRING_ENV",
+            "bool_out",
+            "map_of_string_json",
+            "number_0_copy",
+            "number_float_hcl"
+        ]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_prune() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let workspace_id = server.add_workspace(ORGANIZATION_NAME, "ws", None);
+        server.add_variable(
+            &workspace_id,
+            json!({"key": "stale", "description": format!("stale {}", MARKER)}),
+        );
+        server.add_variable(
+            &workspace_id,
+            json!({"key": "other_source", "description": "[managed by tfvar-export from other]"}),
+        );
+        // Same name as an exported one but another category
+        server.add_variable(
+            &workspace_id,
+            json!({
+                "key": "number_0_out",
+                "category": "env",
+                "description": MARKER
+            }),
+        );
+        server.add_variable(
+            &workspace_id,
+            json!({"key": "by_hand", "description": "created by hand"}),
+        );
+
+        // Nothing is deleted without `--prune`.
+        run_mock(&server, &["-t", "ws"]).await.unwrap();
+        assert_eq!(server.variable_keys(&workspace_id), vec![
+            "by_hand",
+            "number_0_out",
+            "number_0_out",
+            "other_source",
+            "set_of_object_out",
+            "stale",
+            "string_out"
+        ]);
+
+        // Only variables managed from the same source and no longer exported are deleted.
+        run_mock(&server, &["-t", "ws", "--prune"]).await.unwrap();
+        assert_eq!(server.variable_keys(&workspace_id), vec![
+            "by_hand",
+            "number_0_out",
+            "other_source",
+            "set_of_object_out",
+            "string_out"
+        ]);
+        assert!(server
+            .variable(&workspace_id, "number_0_out", VariableCategory::Env)
+            .is_none());
+
+        // Managed variables created by the previous runs are deleted once removed from the list.
+        run_mock_with_export_list(
+            &server,
+            &["-t", "ws", "--prune"],
+            "files/test/export_list.with_options.txt",
+        )
+        .await
+        .unwrap();
+        assert_eq!(server.variable_keys(&workspace_id), vec![
+            "SECRET",
+            "STRING_ENV",
+            "by_hand",
+            "map_of_string_json",
+            "number_0_copy",
+            "number_float_hcl",
+            "other_source"
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_run_export_list_options() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let workspace_id = server.add_workspace(ORGANIZATION_NAME, "ws", None);
+        // A Terraform variable of the same name does not conflict with an environment variable.
+        server.add_variable(&workspace_id, json!({"key": "STRING_ENV", "value": "old"}));
+
+        run_mock_with_export_list(
+            &server,
+            &["-t", "ws"],
+            "files/test/export_list.with_options.txt",
+        )
+        .await
+        .unwrap();
+        assert_eq!(server.variable_keys(&workspace_id), vec![
+            "SECRET",
+            "STRING_ENV",
+            "STRING_ENV",
+            "map_of_string_json",
+            "number_0_copy",
+            "number_float_hcl"
+        ]);
+        let variable = server
+            .variable(&workspace_id, "STRING_ENV", VariableCategory::Env)
+            .unwrap();
+        assert_eq!(variable["value"], json!("aaa"));
+        assert_eq!(variable["hcl"], json!(false));
+        assert_eq!(
+            server
+                .variable(&workspace_id, "STRING_ENV", VariableCategory::Terraform)
+                .unwrap()["value"],
+            json!("old")
+        );
+
+        // Sensitive output opted in
+        let variable = server
+            .variable(&workspace_id, "SECRET", VariableCategory::Env)
+            .unwrap();
+        assert_eq!(variable["value"], json!("**************"));
+        assert_eq!(variable["sensitive"], json!(true));
+
+        // Encodings
+        let variable = server
+            .variable(
+                &workspace_id,
+                "map_of_string_json",
+                VariableCategory::Terraform,
+            )
+            .unwrap();
+        assert_eq!(
+            variable["value"],
+            json!(r#"{"a":"aaa","b":"bbb","c":"ccc"}"#)
+        );
+        assert_eq!(variable["hcl"], json!(false));
+        let variable = server
+            .variable(
+                &workspace_id,
+                "number_float_hcl",
+                VariableCategory::Terraform,
+            )
+            .unwrap();
+        assert_eq!(variable["value"], json!("1.2345"));
+        assert_eq!(variable["hcl"], json!(true));
+    }
+
+    #[tokio::test]
+    async fn test_run_pagination() {
+        let server = MockTerraformServer::start().await;
+        server.set_max_page_size(10);
+        server.add_organization(ORGANIZATION_NAME);
+        let project_ids: Vec<String> = (0..15)
+            .map(|i| server.add_project(ORGANIZATION_NAME, &format!("project-{}", i)))
+            .collect();
+        let workspace_ids: Vec<String> = (0..35)
+            .map(|i| {
+                server.add_workspace(
+                    ORGANIZATION_NAME,
+                    &format!("ws-{}", i),
+                    Some(project_ids.get(i % 15).unwrap()),
                 )
             })
             .collect();
 
-        if 0 < vars_new.len() {
-            let create_variable_result =
-                create_variable(&workspace_id, &api_conn_prop, &vars_new).await?;
-            println!("Variable(s) created: {:#?}", create_variable_result);
+        // Workspaces on the first and the last page
+        run_mock(&server, &["--target-regex", "^ws-(0|34)$"])
+            .await
+            .unwrap();
+        assert_eq!(
+            server.variable_keys(workspace_ids.first().unwrap()).len(),
+            3
+        );
+        assert_eq!(server.variable_keys(workspace_ids.last().unwrap()).len(), 3);
+
+        let requests = server.received_requests().await;
+        let count = |path: &str| requests.iter().filter(|val| val.ends_with(path)).count();
+        assert_eq!(count("/workspaces"), 4);
+        assert_eq!(count("/projects"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_workspace_ids() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let ws_by_id = server.add_workspace(ORGANIZATION_NAME, "ws-by-id", None);
+        let ws_by_name = server.add_workspace(ORGANIZATION_NAME, "ws-by-name", None);
+        let ws_excluded = server.add_workspace(ORGANIZATION_NAME, "ws-excluded", None);
+
+        // Got one by one without listing the organization
+        run_mock(&server, &[
+            "-t",
+            &format!("{},ws-by-name,ws-unknown,{}", ws_by_id, ws_excluded),
+            "--exclude-workspaces",
+            &ws_excluded,
+        ])
+        .await
+        .unwrap();
+        assert_eq!(server.variable_keys(&ws_by_id).len(), 3);
+        assert_eq!(server.variable_keys(&ws_by_name).len(), 3);
+        assert!(server.variable_keys(&ws_excluded).is_empty());
+        let requests = server.received_requests().await;
+        assert!(!requests
+            .iter()
+            .any(|val| val.ends_with("/workspaces") || val.ends_with("/projects")));
+        assert!(requests.contains(&String::from(
+            "GET /api/v2/organizations/org/workspaces/ws-by-name"
+        )));
+
+        // IDs do not require `TFVE_ORGANIZATION_NAME` but names do
+        let args = |target: &str| {
+            utils::clap::new_clap_command()
+                .try_get_matches_from([
+                    "tfvar-export",
+                    "--base-url",
+                    &server.uri(),
+                    "-t",
+                    target,
+                    "-u",
+                    "--source",
+                    "outputs.json",
+                    "--auto-approve",
+                    "--backup-dir",
+                    &test_backup_dir(),
+                    "files/test/outputs.json",
+                    "files/test/export_list_construct_export_value.txt",
+                ])
+                .unwrap()
+        };
+        run(&args(&ws_excluded), Some(MOCK_TOKEN.to_string()), None)
+            .await
+            .unwrap();
+        assert_eq!(server.variable_keys(&ws_excluded).len(), 3);
+        assert!(matches!(
+            run(&args("ws-by-name"), Some(MOCK_TOKEN.to_string()), None).await,
+            Err(TfvarExportError::Config(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_errors() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let ws_not_found = server.add_workspace(ORGANIZATION_NAME, "ws-not-found", None);
+        let ws_invalid = server.add_workspace(ORGANIZATION_NAME, "ws-invalid", None);
+        let ws_rate_limited = server.add_workspace(ORGANIZATION_NAME, "ws-rate-limited", None);
+
+        server.inject_error(
+            "GET",
+            &format!("/api/v2/workspaces/{}/vars", ws_not_found),
+            404,
+            1,
+        );
+        server.inject_error(
+            "POST",
+            &format!("/api/v2/workspaces/{}/vars", ws_invalid),
+            422,
+            1,
+        );
+        server.inject_error(
+            "POST",
+            &format!("/api/v2/workspaces/{}/vars", ws_rate_limited),
+            429,
+            2,
+        );
+
+        // A failed workspace does not stop the others, and rate-limited requests are retried.
+        let res = run_mock(&server, &[
+            "-t",
+            "ws-not-found,ws-invalid,ws-rate-limited",
+            "--max-attempts",
+            "3",
+        ])
+        .await;
+        match res {
+            Err(TfvarExportError::WorkspacesFailed(workspaces)) => {
+                assert_eq!(workspaces, vec!["ws-not-found", "ws-invalid"])
+            },
+            res => panic!("Unexpected result: {:?}", res),
         }
+        assert!(server.variable_keys(&ws_not_found).is_empty());
+        assert!(server.variable_keys(&ws_invalid).is_empty());
+        assert_eq!(server.variable_keys(&ws_rate_limited).len(), 3);
+    }
 
-        if allow_update {
-            // Variable(s) already existing
-            let vars_existing: Vec<TerraformVariableProperty> = status
+    #[tokio::test]
+    async fn test_run_target_projects() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let project_a = server.add_project(ORGANIZATION_NAME, "project-a");
+        let project_b = server.add_project(ORGANIZATION_NAME, "project-b");
+        let ws_a1 = server.add_workspace(ORGANIZATION_NAME, "ws-a1", Some(&project_a));
+        let ws_a2 = server.add_workspace(ORGANIZATION_NAME, "ws-a2", Some(&project_a));
+        let ws_b1 = server.add_workspace(ORGANIZATION_NAME, "ws-b1", Some(&project_b));
+        let ws_other = server.add_workspace(ORGANIZATION_NAME, "ws-other", None);
+
+        // Every workspace in the projects and the named ones, except the excluded ones
+        run_mock(&server, &[
+            "-t",
+            "ws-other,ws-a1",
+            "--target-projects",
+            "project-a,project-unknown",
+            "--exclude-workspaces",
+            "ws-a2",
+        ])
+        .await
+        .unwrap();
+        assert_eq!(server.variable_keys(&ws_a1).len(), 3);
+        assert_eq!(server.variable_keys(&ws_other).len(), 3);
+        assert!(server.variable_keys(&ws_a2).is_empty());
+        assert!(server.variable_keys(&ws_b1).is_empty());
+        // Each workspace is processed once even if targeted twice
+        assert_eq!(
+            server
+                .received_requests()
+                .await
                 .iter()
-                .filter(|val| val.get_variable_id().is_some())
-                .map(|val| {
-                    TerraformVariableProperty::new(
-                        Some(val.get_variable_id().clone().unwrap()),
-                        val.get_variable_name().to_owned(),
-                        var_name_val_des_map
-                            .get(val.get_variable_name())
-                            .unwrap()
-                            .0
-                            .to_owned(),
-                        var_name_val_des_map
-                            .get(val.get_variable_name())
-                            .unwrap()
-                            .1
-                            .to_owned(),
-                    )
-                })
-                .collect();
-
-            if 0 < vars_existing.len() {
-                let update_variable_result =
-                    update_variable(&workspace_id, &api_conn_prop, &vars_existing).await?;
-                println!("Variable(s) updated: {:#?}", update_variable_result);
-            }
-        } else {
-            // Variable(s) already existing
-            let vars_existing: Vec<&str> = status
+                .filter(|val| **val == format!("POST /api/v2/workspaces/{}/vars", ws_a1))
+                .count(),
+            3
+        );
+
+        // Projects only
+        run_mock(&server, &["--target-projects", "project-b"])
+            .await
+            .unwrap();
+        assert_eq!(server.variable_keys(&ws_b1).len(), 3);
+        assert!(server.variable_keys(&ws_a2).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_target_tags_patterns() {
+        let server = MockTerraformServer::start().await;
+        server.set_max_page_size(2);
+        server.add_organization(ORGANIZATION_NAME);
+        let workspace = |name: &str, tags: &[&str]| {
+            let id = server.add_workspace(ORGANIZATION_NAME, name, None);
+            server.set_workspace_tags(&id, tags);
+            id
+        };
+        let app_web_prod = workspace("app-web-prod", &["env=prod", "layer=app"]);
+        let app_api_prod = workspace("app-api-prod", &["env=prod", "layer=app"]);
+        let app_web_dev = workspace("app-web-dev", &["env=dev", "layer=app"]);
+        let net_prod = workspace("net-prod", &["env=prod", "layer=network"]);
+        let db_prod = workspace("db-prod", &["env=prod"]);
+
+        // Tags only, over multiple pages
+        run_mock(&server, &["--target-tags", "env=prod,layer=app"])
+            .await
+            .unwrap();
+        assert_eq!(server.variable_keys(&app_web_prod).len(), 3);
+        assert_eq!(server.variable_keys(&app_api_prod).len(), 3);
+        for id in [&app_web_dev, &net_prod, &db_prod] {
+            assert!(server.variable_keys(id).is_empty());
+        }
+
+        // Tags narrowed down by a glob pattern
+        run_mock(&server, &[
+            "--target-tags",
+            "env=prod",
+            "--target-pattern",
+            "*-prod",
+            "--exclude-workspaces",
+            "app-web-prod,app-api-prod",
+        ])
+        .await
+        .unwrap();
+        assert_eq!(server.variable_keys(&net_prod).len(), 3);
+        assert_eq!(server.variable_keys(&db_prod).len(), 3);
+        assert!(server.variable_keys(&app_web_dev).is_empty());
+
+        // Patterns only
+        run_mock(&server, &["--target-regex", "^app-web-(dev|test)$"])
+            .await
+            .unwrap();
+        assert_eq!(server.variable_keys(&app_web_dev).len(), 3);
+
+        // Invalid pattern
+        assert!(matches!(
+            run_mock(&server, &["--target-regex", "app-("]).await,
+            Err(TfvarExportError::Config(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_varset() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+        let project_id = server.add_project(ORGANIZATION_NAME, "network");
+        let workspace_id = server.add_workspace(ORGANIZATION_NAME, "app", None);
+
+        // Created and applied; no target workspaces are required
+        run_mock(&server, &[
+            "--varset",
+            "shared",
+            "--varset-workspaces",
+            "app,no-such-workspace",
+            "--varset-projects",
+            "network",
+        ])
+        .await
+        .unwrap();
+        let varset_id = server.variable_set_id(ORGANIZATION_NAME, "shared").unwrap();
+        assert_eq!(server.variable_keys(&varset_id), vec![
+            "number_0_out",
+            "set_of_object_out",
+            "string_out"
+        ]);
+        assert_eq!(server.variable_set_workspaces(&varset_id), vec![
+            workspace_id.clone()
+        ]);
+        assert_eq!(server.variable_set_projects(&varset_id), vec![project_id]);
+        // Not written into the workspace
+        assert!(server.variable_keys(&workspace_id).is_empty());
+
+        // The existing variable set is updated alongside the target workspaces
+        run_mock(&server, &["--varset", "shared", "-t", "app", "-u"])
+            .await
+            .unwrap();
+        assert_eq!(
+            server
+                .variable(&varset_id, "string_out", VariableCategory::Terraform)
+                .unwrap()["value"],
+            json!("aaa")
+        );
+        assert_eq!(server.variable_keys(&workspace_id).len(), 3);
+        assert_eq!(
+            server
+                .received_requests()
+                .await
+                .iter()
+                .filter(|val| val.starts_with("POST /api/v2/organizations/org/varsets"))
+                .count(),
+            1
+        );
+
+        // Applied to the workspaces and the projects only once
+        run_mock(&server, &[
+            "--varset",
+            "shared",
+            "--varset-workspaces",
+            "app",
+            "--varset-projects",
+            "network",
+            "-u",
+        ])
+        .await
+        .unwrap();
+        let is_apply = |val: &String| {
+            val.starts_with(&format!(
+                "POST /api/v2/varsets/{}/relationships/",
+                varset_id
+            )) && !val.ends_with("/vars")
+        };
+        assert_eq!(
+            server
+                .received_requests()
+                .await
                 .iter()
-                .filter(|val| val.get_variable_id().is_some())
-                .map(|val| val.get_variable_name())
-                .collect();
-            if 0 < vars_existing.len() {
-                warn!(
-                    "Following variable(s) were ignored because they are existing but \
-                     `--allow_update` is not specified: {:#?}",
-                    vars_existing
-                );
+                .filter(|val| is_apply(val))
+                .count(),
+            2
+        );
+        let plan_file = std::env::temp_dir().join(format!("plan-{}.json", uuid::Uuid::new_v4()));
+        let plan_file = plan_file.to_str().unwrap();
+        let clap = utils::clap::new_clap_command()
+            .try_get_matches_from([
+                "tfvar-export",
+                "plan",
+                "--base-url",
+                &server.uri(),
+                "--varset",
+                "shared",
+                "--varset-workspaces",
+                "app",
+                "--varset-projects",
+                "network",
+                "-u",
+                "--source",
+                "outputs.json",
+                "--out",
+                plan_file,
+                "files/test/outputs.json",
+                "files/test/export_list_construct_export_value.txt",
+            ])
+            .unwrap();
+        run(
+            &clap,
+            Some(MOCK_TOKEN.to_string()),
+            Some(ORGANIZATION_NAME.to_string()),
+        )
+        .await
+        .unwrap();
+        assert!(!ExportPlan::load(plan_file).unwrap().has_changes());
+        std::fs::remove_file(plan_file).unwrap();
+
+        // `TFVE_ORGANIZATION_NAME` is required
+        let clap = utils::clap::new_clap_command()
+            .try_get_matches_from([
+                "tfvar-export",
+                "--varset",
+                "shared",
+                "--source",
+                "outputs.json",
+                "a.json",
+                "b.txt",
+            ])
+            .unwrap();
+        assert!(matches!(
+            run(&clap, Some(MOCK_TOKEN.to_string()), None).await,
+            Err(TfvarExportError::Config(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_config_errors() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization(ORGANIZATION_NAME);
+
+        // Unknown organization
+        let clap = utils::clap::new_clap_command()
+            .try_get_matches_from([
+                "tfvar-export",
+                "--base-url",
+                &server.uri(),
+                "--show-workspaces",
+            ])
+            .unwrap();
+        assert!(matches!(
+            run(
+                &clap,
+                Some(MOCK_TOKEN.to_string()),
+                Some(String::from("unknown"))
+            )
+            .await,
+            Err(TfvarExportError::Api { status: 404, .. })
+        ));
+
+        // Invalid token
+        assert!(matches!(
+            run(
+                &clap,
+                Some(String::from("invalid")),
+                Some(ORGANIZATION_NAME.to_string())
+            )
+            .await,
+            Err(TfvarExportError::Api { status: 401, .. })
+        ));
+
+        // Missing environment variables
+        assert!(matches!(
+            run(&clap, None, Some(ORGANIZATION_NAME.to_string())).await,
+            Err(TfvarExportError::Config(_))
+        ));
+        assert!(matches!(
+            run(&clap, Some(MOCK_TOKEN.to_string()), None).await,
+            Err(TfvarExportError::Config(_))
+        ));
+
+        // At least one workspace is processed at a time
+        assert_eq!(
+            utils::clap::new_clap_command()
+                .try_get_matches_from([
+                    "tfvar-export",
+                    "--concurrency",
+                    "0",
+                    "files/test/outputs.json",
+                    "files/test/export_list_construct_export_value.txt",
+                ])
+                .unwrap_err()
+                .kind(),
+            clap::error::ErrorKind::ValueValidation
+        );
+
+        // `--auto-approve` is required unless the confirmation can be asked
+        if !utils::confirm::is_interactive() {
+            let workspace_id = server.add_workspace(ORGANIZATION_NAME, "ws", None);
+            let clap = utils::clap::new_clap_command()
+                .try_get_matches_from([
+                    "tfvar-export",
+                    "--base-url",
+                    &server.uri(),
+                    "-t",
+                    "ws",
+                    "--source",
+                    "outputs.json",
+                    "files/test/outputs.json",
+                    "files/test/export_list_construct_export_value.txt",
+                ])
+                .unwrap();
+            assert!(matches!(
+                run(
+                    &clap,
+                    Some(MOCK_TOKEN.to_string()),
+                    Some(ORGANIZATION_NAME.to_string())
+                )
+                .await,
+                Err(TfvarExportError::Config(_))
+            ));
+            assert!(server.variable_keys(&workspace_id).is_empty());
+
+            for args in [["tfvar-export", "apply", "plan.json"], [
+                "tfvar-export",
+                "restore",
+                "backup.json",
+            ]] {
+                let clap = utils::clap::new_clap_command()
+                    .try_get_matches_from(args)
+                    .unwrap();
+                assert!(matches!(
+                    run(&clap, Some(MOCK_TOKEN.to_string()), None).await,
+                    Err(TfvarExportError::Config(_))
+                ));
             }
         }
     }
-
-    Ok(())
 }
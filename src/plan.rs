@@ -0,0 +1,731 @@
+//! Plan and apply the changes of variables in a workspace or a variable set.
+//!
+//! A plan is computed only by reading the variables, so it can be shown before anything is
+//! written, or saved to a file and applied later.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::TfvarExportError,
+    export::{ExportOptions, ExportResult, TargetWorkspace},
+    terraform_api::{
+        check_variable_status::variable_status,
+        client::TerraformClient,
+        models::{Resource, VarAttributes, VariableCategory},
+        register_variable::{TerraformVariableProperty, VariableTarget},
+    },
+    utils::{
+        construct_export_value::ExportValue,
+        diff::{pretty_value, render_diff},
+        json_file,
+        ownership,
+    },
+};
+
+/// Shown instead of the value of a sensitive variable
+const SENSITIVE_VALUE: &str = "(sensitive value)";
+
+/// Version of the format of plan files
+const PLAN_VERSION: u32 = 1;
+
+/// Reason a variable is skipped
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The variable exists but update is not allowed.
+    UpdateNotAllowed,
+    /// The variable is not managed by this tool from the same source and not adopted.
+    NotOwned,
+}
+
+impl SkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::UpdateNotAllowed => "`--allow-update` is not specified",
+            SkipReason::NotOwned => "not created by this tool from the same source",
+        }
+    }
+}
+
+/// Change of a variable
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableAction {
+    Create,
+    Update,
+    Unchanged,
+    Skip(SkipReason),
+    Prune,
+}
+
+impl VariableAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VariableAction::Create => "create",
+            VariableAction::Update => "update",
+            VariableAction::Unchanged => "unchanged",
+            VariableAction::Skip(_) => "skip",
+            VariableAction::Prune => "prune",
+        }
+    }
+
+    /// Symbol at the head of the line of a variable
+    fn symbol(&self) -> char {
+        match self {
+            VariableAction::Create => '+',
+            VariableAction::Update => '~',
+            VariableAction::Unchanged => '=',
+            VariableAction::Skip(_) => '!',
+            VariableAction::Prune => '-',
+        }
+    }
+
+    /// Whether the action writes to the target.
+    pub fn is_change(&self) -> bool {
+        matches!(
+            self,
+            VariableAction::Create | VariableAction::Update | VariableAction::Prune
+        )
+    }
+}
+
+/// Planned change of a variable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedVariable {
+    action: VariableAction,
+    variable_name: String,
+    category: VariableCategory,
+    /// `None` if the variable does not exist.
+    variable_id: Option<String>,
+    /// `None` if the variable does not exist.
+    current: Option<VarAttributes>,
+    /// `None` if the variable is pruned.
+    desired: Option<TerraformVariableProperty>,
+}
+
+impl PlannedVariable {
+    /// Current value to be shown; sensitive values are never shown.
+    pub fn current_value(&self) -> Option<&str> {
+        self.current.as_ref().map(|val| match val.is_sensitive() {
+            true => SENSITIVE_VALUE,
+            false => val.get_value().as_deref().unwrap_or_default(),
+        })
+    }
+
+    /// Desired value to be shown; sensitive values are never shown.
+    pub fn desired_value(&self) -> Option<String> {
+        self.desired.as_ref().map(|val| match val.is_sensitive() {
+            true => SENSITIVE_VALUE.to_string(),
+            false => val.encode().0,
+        })
+    }
+
+    /// Whether the current or the desired value is sensitive.
+    fn is_sensitive(&self) -> bool {
+        self.current.as_ref().is_some_and(|val| val.is_sensitive())
+            || self.desired.as_ref().is_some_and(|val| val.is_sensitive())
+    }
+
+    /// Line-based diff from the current value to the desired one, both pretty-printed.
+    ///
+    /// Empty unless the variable is updated and neither value is sensitive.
+    pub fn render_diff(&self, color: bool) -> Vec<String> {
+        match (&self.action, &self.current, &self.desired) {
+            (VariableAction::Update, Some(current), Some(desired)) if !self.is_sensitive() => {
+                let old = pretty_value(
+                    current.get_value().as_deref().unwrap_or_default(),
+                    current.is_hcl(),
+                );
+                let (value, is_hcl) = desired.encode();
+                render_diff(&old, &pretty_value(&value, is_hcl), color)
+            },
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A line per variable such as `+ create    key (terraform): "new"`.
+///
+/// The values of an updated variable are rendered by [`PlannedVariable::render_diff`] instead.
+impl std::fmt::Display for PlannedVariable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:<9} {} ({})",
+            self.action.symbol(),
+            self.action.as_str(),
+            self.variable_name,
+            self.category
+        )?;
+        match (self.action, self.current_value(), self.desired_value()) {
+            (VariableAction::Create, _, Some(desired)) => write!(f, ": {:?}", desired),
+            (VariableAction::Update, _, _) if self.is_sensitive() => {
+                write!(f, ": {}", SENSITIVE_VALUE)
+            },
+            (VariableAction::Skip(reason), Some(current), Some(desired)) => {
+                write!(f, ": {:?} -> {:?} ({})", current, desired, reason.as_str())
+            },
+            (VariableAction::Prune, Some(current), _) => write!(f, ": {:?}", current),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Plan of a workspace or a variable set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetPlan {
+    target: VariableTarget,
+    variables: Vec<PlannedVariable>,
+}
+
+impl TargetPlan {
+    pub fn get_target(&self) -> &VariableTarget {
+        &self.target
+    }
+
+    pub fn get_variables(&self) -> &[PlannedVariable] {
+        &self.variables
+    }
+
+    /// Whether applying the plan writes anything to the target.
+    pub fn has_changes(&self) -> bool {
+        self.variables.iter().any(|val| val.action.is_change())
+    }
+
+    /// Names of the planned variables whose current state in `existing_variables` differs from
+    /// the state the plan was made on, including variables created or deleted since then.
+    ///
+    /// The attributes are compared including `version-id`, so that a change of a sensitive value,
+    /// which is never returned, is detected as well.
+    pub fn changed_variables(&self, existing_variables: &[Resource<VarAttributes>]) -> Vec<String> {
+        let existing_variables_map: HashMap<(&str, VariableCategory), &Resource<VarAttributes>> =
+            existing_variables
+                .iter()
+                .map(|val| {
+                    (
+                        (
+                            val.get_attributes().get_key(),
+                            val.get_attributes().get_category(),
+                        ),
+                        val,
+                    )
+                })
+                .collect();
+
+        self.variables
+            .iter()
+            .filter(|val| {
+                let existing = existing_variables_map
+                    .get(&(val.variable_name.as_str(), val.category))
+                    .map(|existing| (existing.get_id(), existing.get_attributes()));
+                existing != val.variable_id.as_deref().zip(val.current.as_ref())
+            })
+            .map(|val| val.variable_name.to_owned())
+            .collect()
+    }
+
+    /// Variables planned to be `action`.
+    fn variables(&self, action: VariableAction) -> Vec<PlannedVariable> {
+        self.variables
+            .iter()
+            .filter(|val| val.action == action)
+            .cloned()
+            .collect()
+    }
+
+    /// Names of the variables planned to be `action`.
+    fn names(&self, action: VariableAction) -> Vec<String> {
+        self.variables
+            .iter()
+            .filter(|val| val.action == action)
+            .map(|val| val.variable_name.to_owned())
+            .collect()
+    }
+
+    /// Desired properties of the variables planned to be `action`.
+    fn properties(&self, action: VariableAction) -> Vec<TerraformVariableProperty> {
+        self.variables
+            .iter()
+            .filter(|val| val.action == action)
+            .filter_map(|val| val.desired.clone())
+            .collect()
+    }
+}
+
+/// Plan the changes to make the variables of `target` match the export values.
+///
+/// Values not existing in the target are created with the ownership marker of the source in their
+/// description. Existing ones are left as they are if already up to date, and otherwise updated
+/// only if `allow_update` is set and they have the marker, or `adopt` is also set to take them
+/// over. With `prune`, variables having the marker but no longer exported are pruned; the other
+/// variables are never pruned.
+pub fn plan_variables(
+    target: &VariableTarget,
+    existing_variables: &[Resource<VarAttributes>],
+    export_values: &[ExportValue],
+    options: &ExportOptions,
+) -> TargetPlan {
+    let var_name_val_des_map: HashMap<(&str, VariableCategory), &ExportValue> = export_values
+        .iter()
+        .map(|val| ((val.get_variable_name(), val.get_category()), val))
+        .collect();
+    let target_variables: Vec<(String, VariableCategory)> = export_values
+        .iter()
+        .map(|val| (val.get_variable_name().to_owned(), val.get_category()))
+        .collect();
+    let marker = options.get_marker();
+
+    let mut variables = Vec::new();
+    for val in variable_status(existing_variables, &target_variables) {
+        let export_value = var_name_val_des_map
+            .get(&(val.get_variable_name(), val.get_category()))
+            .unwrap();
+        let property = TerraformVariableProperty::new(
+            val.get_variable_id().clone(),
+            val.get_variable_name().to_owned(),
+            Some(marker.mark_description(export_value.get_variable_description().as_deref())),
+            export_value.get_value().to_owned(),
+        )
+        .with_category(val.get_category())
+        .with_sensitive(export_value.is_sensitive())
+        .with_encoding(export_value.get_encoding());
+
+        let action = match val.get_attributes() {
+            None => VariableAction::Create,
+            Some(attributes) if property.is_up_to_date(attributes) => VariableAction::Unchanged,
+            Some(_) if !options.is_allow_update() => {
+                VariableAction::Skip(SkipReason::UpdateNotAllowed)
+            },
+            Some(attributes)
+                if options.is_adopt()
+                    || marker.is_owner(attributes.get_description().as_deref()) =>
+            {
+                VariableAction::Update
+            },
+            Some(_) => VariableAction::Skip(SkipReason::NotOwned),
+        };
+        variables.push(PlannedVariable {
+            action,
+            variable_name: val.get_variable_name().to_owned(),
+            category: val.get_category(),
+            variable_id: val.get_variable_id().clone(),
+            current: val.get_attributes().clone(),
+            desired: Some(property),
+        });
+    }
+
+    // Variable(s) managed from the source but no longer exported
+    if options.is_prune() {
+        for val in existing_variables.iter() {
+            let attributes = val.get_attributes();
+            if marker.is_owner(attributes.get_description().as_deref())
+                && !var_name_val_des_map
+                    .contains_key(&(attributes.get_key(), attributes.get_category()))
+            {
+                variables.push(PlannedVariable {
+                    action: VariableAction::Prune,
+                    variable_name: attributes.get_key().to_owned(),
+                    category: attributes.get_category(),
+                    variable_id: Some(val.get_id().to_owned()),
+                    current: Some(attributes.to_owned()),
+                    desired: None,
+                });
+            }
+        }
+    }
+
+    TargetPlan {
+        target: target.to_owned(),
+        variables,
+    }
+}
+
+/// Read the variables of `target` and plan the changes. Nothing is written.
+pub async fn plan_target(
+    client: &TerraformClient,
+    target: &VariableTarget,
+    export_values: &[ExportValue],
+    options: &ExportOptions,
+) -> Result<TargetPlan, TfvarExportError> {
+    log::info!("Processing {}", target);
+    let existing_variables = client.get_variables(target).await?;
+
+    Ok(plan_variables(
+        target,
+        &existing_variables,
+        export_values,
+        options,
+    ))
+}
+
+/// Create, update and delete the variables as planned.
+pub async fn apply_plan(
+    client: &TerraformClient,
+    plan: &TargetPlan,
+) -> Result<ExportResult, TfvarExportError> {
+    let target = plan.get_target();
+
+    let vars_new = plan.properties(VariableAction::Create);
+    let created = if vars_new.is_empty() {
+        Vec::new()
+    } else {
+        client.create_variable(target, &vars_new).await?
+    };
+
+    let vars_existing = plan.properties(VariableAction::Update);
+    if !vars_existing.is_empty() {
+        client.update_variable(target, &vars_existing).await?;
+    }
+
+    let vars_pruned: Vec<String> = plan
+        .variables
+        .iter()
+        .filter(|val| val.action == VariableAction::Prune)
+        .filter_map(|val| val.variable_id.clone())
+        .collect();
+    if !vars_pruned.is_empty() {
+        client.delete_variable(target, &vars_pruned).await?;
+    }
+
+    Ok(ExportResult::new(
+        created,
+        plan.variables(VariableAction::Update),
+        plan.names(VariableAction::Unchanged),
+        plan.names(VariableAction::Skip(SkipReason::UpdateNotAllowed)),
+        plan.names(VariableAction::Skip(SkipReason::NotOwned)),
+        plan.names(VariableAction::Prune),
+    ))
+}
+
+/// Plan of a variable set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VarsetPlan {
+    organization_name: String,
+    varset_name: String,
+    /// `None` if the variable set is to be created.
+    varset_id: Option<String>,
+    /// IDs of the workspaces the variable set is to be applied to, excluding the ones it already
+    /// is
+    workspace_ids: Vec<String>,
+    /// IDs of the projects the variable set is to be applied to, excluding the ones it already is
+    project_ids: Vec<String>,
+    variables: Vec<PlannedVariable>,
+}
+
+impl VarsetPlan {
+    pub fn new(
+        organization_name: String,
+        varset_name: String,
+        varset_id: Option<String>,
+        plan: TargetPlan,
+    ) -> Self {
+        Self {
+            organization_name,
+            varset_name,
+            varset_id,
+            workspace_ids: Vec::new(),
+            project_ids: Vec::new(),
+            variables: plan.variables,
+        }
+    }
+
+    pub fn with_workspace_ids(mut self, workspace_ids: Vec<String>) -> Self {
+        self.workspace_ids = workspace_ids;
+        self
+    }
+
+    pub fn with_project_ids(mut self, project_ids: Vec<String>) -> Self {
+        self.project_ids = project_ids;
+        self
+    }
+
+    pub fn get_organization_name(&self) -> &str {
+        &self.organization_name
+    }
+
+    pub fn get_varset_name(&self) -> &str {
+        &self.varset_name
+    }
+
+    pub fn get_varset_id(&self) -> &Option<String> {
+        &self.varset_id
+    }
+
+    pub fn get_workspace_ids(&self) -> &[String] {
+        &self.workspace_ids
+    }
+
+    pub fn get_project_ids(&self) -> &[String] {
+        &self.project_ids
+    }
+
+    /// Whether applying the plan writes anything, including creating the variable set and
+    /// applying it to the workspaces and the projects.
+    pub fn has_changes(&self) -> bool {
+        self.varset_id.is_none()
+            || !self.workspace_ids.is_empty()
+            || !self.project_ids.is_empty()
+            || self.variables.iter().any(|val| val.action.is_change())
+    }
+
+    /// Plan of the variables of the variable set `varset_id`.
+    pub fn target_plan(&self, varset_id: &str) -> TargetPlan {
+        TargetPlan {
+            target: VariableTarget::VariableSet(varset_id.to_owned()),
+            variables: self.variables.clone(),
+        }
+    }
+}
+
+/// Plan of a workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspacePlan {
+    workspace: TargetWorkspace,
+    plan: TargetPlan,
+}
+
+impl WorkspacePlan {
+    pub fn new(workspace: TargetWorkspace, plan: TargetPlan) -> Self {
+        Self { workspace, plan }
+    }
+
+    pub fn get_workspace(&self) -> &TargetWorkspace {
+        &self.workspace
+    }
+
+    pub fn get_plan(&self) -> &TargetPlan {
+        &self.plan
+    }
+}
+
+/// Plan of the variable set and the workspaces, which can be saved to a file
+///
+/// The file records the targets, the IDs and the current state of the variables and the desired
+/// values including sensitive ones, so it must be kept as safe as the values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportPlan {
+    version: u32,
+    varset: Option<VarsetPlan>,
+    workspaces: Vec<WorkspacePlan>,
+}
+
+impl ExportPlan {
+    pub fn new(varset: Option<VarsetPlan>, workspaces: Vec<WorkspacePlan>) -> Self {
+        Self {
+            version: PLAN_VERSION,
+            varset,
+            workspaces,
+        }
+    }
+
+    pub fn get_varset(&self) -> &Option<VarsetPlan> {
+        &self.varset
+    }
+
+    pub fn get_workspaces(&self) -> &[WorkspacePlan] {
+        &self.workspaces
+    }
+
+    /// Whether applying the plan writes anything to the variable set or the workspaces.
+    pub fn has_changes(&self) -> bool {
+        self.varset.as_ref().is_some_and(|val| val.has_changes())
+            || self.workspaces.iter().any(|val| val.plan.has_changes())
+    }
+
+    /// Sources in the ownership markers of the variables to be written.
+    pub fn sources(&self) -> Vec<String> {
+        let mut sources: Vec<String> = self
+            .varset
+            .iter()
+            .flat_map(|val| val.variables.iter())
+            .chain(self.workspaces.iter().flat_map(|val| val.plan.variables.iter()))
+            .filter(|val| val.action.is_change())
+            .filter_map(|val| val.desired.as_ref())
+            .filter_map(|val| ownership::marker_source(val.get_variable_description().as_deref()))
+            .map(|val| val.to_string())
+            .collect();
+        sources.sort();
+        sources.dedup();
+
+        sources
+    }
+
+    /// Names and targets of the existing variable set and workspaces whose variables are changed.
+    pub fn changed_targets(&self) -> Vec<(String, VariableTarget)> {
+        let varset = self.varset.iter().filter_map(|val| {
+            val.varset_id
+                .as_ref()
+                .filter(|_| val.variables.iter().any(|v| v.action.is_change()))
+                .map(|id| {
+                    (
+                        val.varset_name.to_owned(),
+                        VariableTarget::VariableSet(id.to_owned()),
+                    )
+                })
+        });
+        let workspaces = self
+            .workspaces
+            .iter()
+            .filter(|val| val.plan.has_changes())
+            .map(|val| {
+                (
+                    val.workspace.get_workspace_name().to_owned(),
+                    val.plan.target.to_owned(),
+                )
+            });
+
+        varset.chain(workspaces).collect()
+    }
+
+    /// Save the plan to `path`, which is readable only by the owner on Unix.
+    pub fn save(&self, path: &str) -> Result<(), TfvarExportError> {
+        json_file::save(path, self)
+    }
+
+    /// Load a plan saved by [`ExportPlan::save`].
+    pub fn load(path: &str) -> Result<Self, TfvarExportError> {
+        let plan: Self = json_file::load(path)?;
+        if plan.version != PLAN_VERSION {
+            return Err(TfvarExportError::Validation(format!(
+                "`{}`: Unsupported plan version {}, expected {}.",
+                path, plan.version, PLAN_VERSION
+            )));
+        }
+
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_plan_variables() {
+        let marker = "[managed by tfvar-export from outputs.json]";
+        let existing_variables: Vec<Resource<VarAttributes>> = serde_json::from_value(json!([
+            {
+                "id": "var-1",
+                "type": "vars",
+                "attributes": {
+                    "key": "unchanged",
+                    "value": "aaa",
+                    "description": marker,
+                    "category": "terraform"
+                }
+            },
+            {
+                "id": "var-2",
+                "type": "vars",
+                "attributes": {
+                    "key": "update",
+                    "value": "old",
+                    "description": marker,
+                    "category": "terraform"
+                }
+            },
+            {
+                "id": "var-3",
+                "type": "vars",
+                "attributes": {
+                    "key": "by_hand",
+                    "value": "old",
+                    "description": "",
+                    "category": "terraform"
+                }
+            },
+            {
+                "id": "var-4",
+                "type": "vars",
+                "attributes": {
+                    "key": "stale",
+                    "value": "old",
+                    "description": marker,
+                    "category": "env"
+                }
+            }
+        ]))
+        .unwrap();
+        let export_values: Vec<ExportValue> = ["unchanged", "update", "by_hand", "create"]
+            .iter()
+            .map(|val| ExportValue::new(val.to_string(), None, json!("aaa")))
+            .collect();
+        let target = VariableTarget::Workspace(String::from("ws-1"));
+        let plan = |options: ExportOptions| {
+            plan_variables(&target, &existing_variables, &export_values, &options)
+                .get_variables()
+                .iter()
+                .map(|val| val.to_string())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(plan(ExportOptions::new("outputs.json")), vec![
+            "= unchanged unchanged (terraform)",
+            r#"! skip      update (terraform): "old" -> "aaa" (`--allow-update` is not specified)"#,
+            r#"! skip      by_hand (terraform): "old" -> "aaa" (`--allow-update` is not specified)"#,
+            r#"+ create    create (terraform): "aaa""#,
+        ]);
+        assert_eq!(
+            plan(
+                ExportOptions::new("outputs.json")
+                    .with_allow_update(true)
+                    .with_prune(true)
+            ),
+            vec![
+                "= unchanged unchanged (terraform)",
+                "~ update    update (terraform)",
+                r#"! skip      by_hand (terraform): "old" -> "aaa" (not created by this tool from the same source)"#,
+                r#"+ create    create (terraform): "aaa""#,
+                r#"- prune     stale (env): "old""#,
+            ]
+        );
+        assert!(!plan_variables(
+            &target,
+            &existing_variables,
+            &export_values[..1],
+            &ExportOptions::new("outputs.json")
+        )
+        .has_changes());
+    }
+
+    #[test]
+    fn test_render_diff() {
+        let existing_variables: Vec<Resource<VarAttributes>> = serde_json::from_value(json!([{
+            "id": "var-1",
+            "type": "vars",
+            "attributes": {
+                "key": "tags",
+                "value": "{ env = \"dev\", team = \"network\" }",
+                "description": "[managed by tfvar-export from outputs.json]",
+                "category": "terraform",
+                "hcl": true
+            }
+        }]))
+        .unwrap();
+        let export_values = vec![ExportValue::new(
+            String::from("tags"),
+            None,
+            json!({"env": "prod", "team": "network"}),
+        )];
+        let plan = plan_variables(
+            &VariableTarget::Workspace(String::from("ws-1")),
+            &existing_variables,
+            &export_values,
+            &ExportOptions::new("outputs.json").with_allow_update(true),
+        );
+
+        let variable = plan.get_variables().first().unwrap();
+        assert_eq!(variable.to_string(), "~ update    tags (terraform)");
+        assert_eq!(variable.render_diff(false), vec![
+            "  {",
+            "-   \"env\" = \"dev\"",
+            "+   \"env\" = \"prod\"",
+            "    \"team\" = \"network\"",
+            "  }",
+        ]);
+    }
+}
@@ -1,4 +1,14 @@
+#[cfg(test)]
+pub mod cassette;
 pub mod check_variable_status;
+pub mod client;
 pub mod connection_prop;
 pub mod get_workspaces;
+#[cfg(test)]
+pub mod mock_server;
+pub mod models;
+pub mod pagination;
+pub mod rate_limiter;
 pub mod register_variable;
+pub mod retry;
+pub mod variable_set;
@@ -0,0 +1,550 @@
+//! Record and replay of HTTP interactions with HCP Terraform API for tests.
+//!
+//! A cassette is a JSON file under `files/test/cassettes/` holding the requests sent by
+//! [`TerraformClient`] and the responses to them, in order.
+//!
+//! - Replay (default): requests are checked against the cassette and answered from it without
+//!   network access.
+//! - Record (`TFVE_CASSETTE_MODE=record`): requests are sent to the real API and the cassette is
+//!   overwritten. `TFVE_TOKEN` and the environment variables of the test are required.
+//!
+//! Cassettes are only recorded against the real API, so that replaying them checks the responses
+//! of the real API. The tests using them are ignored until their cassettes are recorded.
+//!
+//! Request headers are never saved, and the values of the environment variables of a test (the
+//! token, the organization name, workspace IDs, ...) and of sensitive variables are scrubbed.
+//! Response bodies are scrubbed as well: resource IDs, `version-id`s and the names of workspaces,
+//! projects and variable sets are replaced with placeholders consistently across the cassette, and
+//! so are the values of variables the test did not write.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::TfvarExportError,
+    terraform_api::{client::TerraformClient, connection_prop::TerraformApiConnectionProperty},
+};
+
+/// Directory of cassette files
+const CASSETTE_DIR: &str = "files/test/cassettes";
+
+/// Base URL of the API to record
+const RECORD_BASE_URL: &str = "https://app.terraform.io";
+
+/// Replacement of the values of sensitive variables
+const SCRUBBED: &str = "[SCRUBBED]";
+
+/// Response headers recorded; the retry policy reads the rate limit ones.
+const RECORDED_HEADERS: [&str; 4] = [
+    "content-type",
+    "retry-after",
+    "x-ratelimit-limit",
+    "x-ratelimit-reset",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CassetteMode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    /// Path and query
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Default)]
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    /// Index of the next interaction to replay
+    position: usize,
+    /// `(value, placeholder)` scrubbed in record mode
+    substitutions: Vec<(String, String)>,
+    /// `(name, placeholder)` of the resources scrubbed from responses, replaced in request paths
+    names: Vec<(String, String)>,
+    /// `(version-id, placeholder)` of the variables scrubbed from responses
+    version_ids: Vec<(String, String)>,
+    /// Keys of the variables written by the test, whose values are kept in responses
+    written_keys: HashSet<String>,
+}
+
+impl CassetteState {
+    /// Replace the scrubbed values in `val`.
+    fn scrub(&self, val: &str) -> String {
+        self.substitutions
+            .iter()
+            .fold(val.to_string(), |acc, (value, placeholder)| {
+                acc.replace(value, placeholder)
+            })
+    }
+
+    /// Replace the scrubbed values and names in the path and query of a request.
+    fn scrub_path(&self, path: &str) -> String {
+        let path = self.scrub(path);
+        let (path, query) = match path.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (path.as_str(), None),
+        };
+        let path = path
+            .split('/')
+            .map(|segment| {
+                self.names
+                    .iter()
+                    .find(|(name, _)| name == segment)
+                    .map_or(segment, |(_, placeholder)| placeholder)
+            })
+            .collect::<Vec<&str>>()
+            .join("/");
+        match query {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        }
+    }
+
+    /// Remember the key of the variable written by a Create/Update variable request.
+    fn note_written_key(&mut self, body: &serde_json::Value) {
+        if let Some(key) = body["data"]["attributes"]["key"].as_str() {
+            self.written_keys.insert(key.to_string());
+        }
+    }
+
+    /// Replace `value` with a placeholder made by `placeholder` from now on, unless it is already
+    /// scrubbed.
+    fn add_substitution(&mut self, value: &str, placeholder: impl FnOnce(usize) -> String) {
+        if self.substitutions.iter().any(|(val, _)| val == value)
+            || self.substitutions.iter().any(|(_, val)| val == value)
+        {
+            return;
+        }
+        let placeholder = placeholder(self.substitutions.len());
+        self.substitutions.push((value.to_string(), placeholder));
+    }
+
+    /// Scrub a response body.
+    fn scrub_response(&mut self, text: &str) -> serde_json::Value {
+        let mut body = match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(body) => body,
+            Err(_) => return serde_json::Value::String(self.scrub(text)),
+        };
+
+        let mut ids = Vec::new();
+        for resource in resources(&mut body) {
+            ids.extend(resource["id"].as_str().map(str::to_string));
+            // Resources may be referred to before they are read, such as the project of a workspace.
+            if let Some(relationships) = resource
+                .get_mut("relationships")
+                .and_then(|val| val.as_object_mut())
+            {
+                for relationship in relationships.values_mut() {
+                    ids.extend(
+                        resources(relationship)
+                            .iter()
+                            .filter_map(|val| val["id"].as_str().map(str::to_string)),
+                    );
+                }
+            }
+        }
+        for id in ids.iter() {
+            let prefix = id.split_once('-').map_or("id", |(prefix, _)| prefix);
+            self.add_substitution(id, |n| format!("{}-scrubbed-{}", prefix, n));
+        }
+        let mut body: serde_json::Value = serde_json::from_str(&self.scrub(&body.to_string()))
+            .expect("Scrubbed response body should be valid JSON.");
+
+        for resource in resources(&mut body) {
+            let resource_type = resource["type"].as_str().unwrap_or_default().to_string();
+            let Some(attributes) = resource.get_mut("attributes") else {
+                continue;
+            };
+            if let Some(version_id) = attributes["version-id"].as_str() {
+                attributes["version-id"] = placeholder(&mut self.version_ids, version_id, "version");
+            }
+            match resource_type.as_str() {
+                "vars" => {
+                    let key = attributes["key"].as_str().unwrap_or_default();
+                    if !self.written_keys.contains(key) && !attributes["value"].is_null() {
+                        attributes["value"] = serde_json::Value::String(SCRUBBED.to_string());
+                    }
+                },
+                "workspaces" | "projects" | "varsets" => {
+                    if let Some(name) = attributes["name"].as_str() {
+                        attributes["name"] = placeholder(
+                            &mut self.names,
+                            name,
+                            resource_type.trim_end_matches('s'),
+                        );
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        body
+    }
+}
+
+/// Interactions of a test
+#[derive(Debug)]
+pub struct Cassette {
+    name: String,
+    mode: CassetteMode,
+    state: Mutex<CassetteState>,
+}
+
+impl Cassette {
+    /// Load the cassette `name`, or start recording it if `TFVE_CASSETTE_MODE` is `record`.
+    pub fn new(name: &str) -> Self {
+        let mode = match std::env::var("TFVE_CASSETTE_MODE").as_deref() {
+            Ok("record") => CassetteMode::Record,
+            _ => CassetteMode::Replay,
+        };
+
+        let mut state = CassetteState::default();
+        if mode == CassetteMode::Replay {
+            let content = std::fs::read_to_string(cassette_path(name)).unwrap_or_else(|err| {
+                panic!(
+                    "Failed to read the cassette `{}`: {}. Record it with \
+                     `TFVE_CASSETTE_MODE=record`.",
+                    name, err
+                )
+            });
+            state.interactions = serde_json::from_str(&content).unwrap();
+        }
+
+        Self {
+            name: name.to_string(),
+            mode,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Value of the environment variable `name` in record mode, or `placeholder` in replay mode.
+    ///
+    /// The value is replaced with `placeholder` in the cassette.
+    pub fn env(&self, name: &str, placeholder: &str) -> String {
+        let mut state = self.state.lock().unwrap();
+        let value = match self.mode {
+            CassetteMode::Record => std::env::var(name).unwrap_or_else(|_| {
+                panic!("Environment variable `{}` is required to record.", name)
+            }),
+            CassetteMode::Replay => return placeholder.to_string(),
+        };
+        state
+            .substitutions
+            .push((value.clone(), placeholder.to_string()));
+        value
+    }
+
+    /// Client sending its requests through the cassette.
+    pub fn client(self: &std::sync::Arc<Self>) -> TerraformClient {
+        let token = self.env("TFVE_TOKEN", "[TOKEN]");
+        TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(RECORD_BASE_URL).unwrap(),
+            token,
+        ))
+        .unwrap()
+        .with_cassette(self.clone())
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.mode == CassetteMode::Replay
+    }
+
+    /// Answer a request with the next interaction.
+    ///
+    /// Panics if the request differs from the recorded one.
+    pub fn replay(
+        &self,
+        method: &reqwest::Method,
+        url: &url::Url,
+        body: Option<&serde_json::Value>,
+    ) -> reqwest::Response {
+        let mut state = self.state.lock().unwrap();
+        let request = RecordedRequest {
+            method: method.to_string(),
+            path: path_and_query(url),
+            body: body.map(scrub_sensitive_value),
+        };
+        let interaction = state.interactions.get(state.position).unwrap_or_else(|| {
+            panic!(
+                "Cassette `{}`: no interaction left for {:#?}",
+                self.name, request
+            )
+        });
+        assert_eq!(
+            interaction.request, request,
+            "Cassette `{}`: request #{} differs from the recorded one (left)",
+            self.name, state.position
+        );
+
+        let recorded = interaction.response.clone();
+        state.position += 1;
+
+        let mut response = http::Response::builder().status(recorded.status);
+        for (name, val) in recorded.headers.iter() {
+            response = response.header(name, val);
+        }
+        let body = match recorded.body {
+            Some(serde_json::Value::String(val)) => val,
+            Some(val) => val.to_string(),
+            None => String::new(),
+        };
+        reqwest::Response::from(response.body(body).unwrap())
+    }
+
+    /// Record a response and return an equivalent one, as the body is consumed.
+    pub async fn record(
+        &self,
+        method: &reqwest::Method,
+        url: &url::Url,
+        body: Option<&serde_json::Value>,
+        response: reqwest::Response,
+    ) -> Result<reqwest::Response, TfvarExportError> {
+        let status = response.status().as_u16();
+        let headers: BTreeMap<String, String> = RECORDED_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let val = response.headers().get(*name)?.to_str().ok()?;
+                Some((name.to_string(), val.to_string()))
+            })
+            .collect();
+        let text = response.text().await?;
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(body) = body {
+            state.note_written_key(body);
+        }
+        let response_body = match text.is_empty() {
+            true => None,
+            false => Some(state.scrub_response(&text)),
+        };
+        let interaction = Interaction {
+            request: RecordedRequest {
+                method: method.to_string(),
+                path: state.scrub_path(&path_and_query(url)),
+                body: body
+                    .map(scrub_sensitive_value)
+                    .map(|val| serde_json::from_str(&state.scrub(&val.to_string())).unwrap()),
+            },
+            response: RecordedResponse {
+                status,
+                headers,
+                body: response_body,
+            },
+        };
+        state.interactions.push(interaction);
+
+        let mut replayed = http::Response::builder().status(status);
+        for (name, val) in state.interactions.last().unwrap().response.headers.iter() {
+            replayed = replayed.header(name, val);
+        }
+        Ok(reqwest::Response::from(replayed.body(text).unwrap()))
+    }
+
+    /// Save the cassette in record mode, or check every interaction was replayed.
+    pub fn finish(&self) {
+        let state = self.state.lock().unwrap();
+        match self.mode {
+            CassetteMode::Record => {
+                std::fs::create_dir_all(CASSETTE_DIR).unwrap();
+                let content = serde_json::to_string_pretty(&state.interactions).unwrap();
+                std::fs::write(cassette_path(&self.name), content + "\n").unwrap();
+            },
+            CassetteMode::Replay => assert_eq!(
+                state.position,
+                state.interactions.len(),
+                "Cassette `{}`: not every interaction was replayed",
+                self.name
+            ),
+        }
+    }
+}
+
+fn cassette_path(name: &str) -> String {
+    format!("{}/{}.json", CASSETTE_DIR, name)
+}
+
+fn path_and_query(url: &url::Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+/// Placeholder of `value` in `placeholders`, added with `prefix` if missing.
+fn placeholder(
+    placeholders: &mut Vec<(String, String)>,
+    value: &str,
+    prefix: &str,
+) -> serde_json::Value {
+    let placeholder = match placeholders.iter().find(|(val, _)| val == value) {
+        Some((_, placeholder)) => placeholder.to_owned(),
+        None => {
+            let placeholder = format!("{}-scrubbed-{}", prefix, placeholders.len());
+            placeholders.push((value.to_string(), placeholder.clone()));
+            placeholder
+        },
+    };
+    serde_json::Value::String(placeholder)
+}
+
+/// Resource objects of a JSON:API document, in `data` and `included`, or resource identifiers of a
+/// relationship.
+fn resources(body: &mut serde_json::Value) -> Vec<&mut serde_json::Value> {
+    let Some(body) = body.as_object_mut() else {
+        return Vec::new();
+    };
+    body.iter_mut()
+        .filter(|(name, _)| *name == "data" || *name == "included")
+        .flat_map(|(_, val)| match val {
+            serde_json::Value::Array(resources) => resources.iter_mut().collect(),
+            serde_json::Value::Object(_) => vec![val],
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// Replace the value of a sensitive variable in a Create/Update variable request.
+fn scrub_sensitive_value(body: &serde_json::Value) -> serde_json::Value {
+    let mut body = body.clone();
+    let attributes = &mut body["data"]["attributes"];
+    if attributes["sensitive"] == true && attributes.get("value").is_some() {
+        attributes["value"] = serde_json::Value::String(SCRUBBED.to_string());
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_scrub_sensitive_value() {
+        let body = json!({
+            "data": {"type": "vars", "attributes": {"key": "a", "value": "b", "sensitive": true}}
+        });
+        assert_eq!(
+            scrub_sensitive_value(&body)["data"]["attributes"]["value"],
+            json!(SCRUBBED)
+        );
+
+        let body = json!({
+            "data": {"type": "vars", "attributes": {"key": "a", "value": "b", "sensitive": false}}
+        });
+        assert_eq!(scrub_sensitive_value(&body), body);
+    }
+
+    #[test]
+    fn test_scrub_response() {
+        let mut state = CassetteState::default();
+        state
+            .substitutions
+            .push((String::from("ws-aBcD1234eFgH5678"), String::from("ws-testing")));
+        state.note_written_key(&json!({"data": {"attributes": {"key": "written"}}}));
+
+        let body = state.scrub_response(
+            &json!({
+                "data": [
+                    {
+                        "id": "var-0123456789abcdef",
+                        "type": "vars",
+                        "attributes": {"key": "written", "value": "kept", "version-id": "v1"},
+                        "relationships": {
+                            "configurable": {"data": {"id": "ws-aBcD1234eFgH5678"}}
+                        }
+                    },
+                    {
+                        "id": "var-fedcba9876543210",
+                        "type": "vars",
+                        "attributes": {"key": "other", "value": "secret", "version-id": "v2"}
+                    },
+                    {
+                        "id": "var-0000000000000000",
+                        "type": "vars",
+                        "attributes": {"key": "sensitive", "value": null, "version-id": "v3"}
+                    }
+                ],
+                "included": [
+                    {
+                        "id": "ws-aBcD1234eFgH5678",
+                        "type": "workspaces",
+                        "attributes": {"name": "production"}
+                    },
+                    {
+                        "id": "prj-aBcD1234eFgH5678",
+                        "type": "projects",
+                        "attributes": {"name": "platform"}
+                    }
+                ]
+            })
+            .to_string(),
+        );
+        let text = body.to_string();
+        for val in [
+            "var-0123456789abcdef",
+            "var-fedcba9876543210",
+            "ws-aBcD1234eFgH5678",
+            "prj-aBcD1234eFgH5678",
+            "\"v1\"",
+            "secret",
+            "production",
+            "platform",
+        ] {
+            assert!(!text.contains(val), "`{}` is not scrubbed: {}", val, text);
+        }
+        assert_eq!(body["data"][0]["attributes"]["value"], json!("kept"));
+        assert_eq!(body["data"][1]["attributes"]["value"], json!(SCRUBBED));
+        assert_eq!(body["data"][2]["attributes"]["value"], json!(null));
+        assert_eq!(
+            body["data"][0]["relationships"]["configurable"]["data"]["id"],
+            json!("ws-testing")
+        );
+        assert_eq!(body["included"][0]["id"], json!("ws-testing"));
+
+        // Later requests refer to the scrubbed resources by their placeholders.
+        let var_id = body["data"][0]["id"].as_str().unwrap();
+        let project_name = body["included"][1]["attributes"]["name"].as_str().unwrap();
+        assert_eq!(
+            state.scrub_path("/api/v2/workspaces/ws-aBcD1234eFgH5678/vars/var-0123456789abcdef"),
+            format!("/api/v2/workspaces/ws-testing/vars/{}", var_id)
+        );
+        assert_eq!(
+            state.scrub_path("/api/v2/organizations/org/projects/platform?page%5Bsize%5D=100"),
+            format!(
+                "/api/v2/organizations/org/projects/{}?page%5Bsize%5D=100",
+                project_name
+            )
+        );
+
+        // Not JSON
+        assert_eq!(
+            state.scrub_response("ws-aBcD1234eFgH5678 not found"),
+            json!("ws-testing not found")
+        );
+    }
+}
@@ -2,13 +2,23 @@
 
 use std::collections::HashMap;
 
-use crate::terraform_api::connection_prop::TerraformApiConnectionProperty;
+use crate::{
+    error::TfvarExportError,
+    terraform_api::{
+        client::TerraformClient,
+        models::{Resource, VarAttributes, VariableCategory},
+        register_variable::VariableTarget,
+    },
+};
 
 /// Terraform variable status
 #[derive(Debug, Eq, PartialEq)]
 pub struct TerraformVariableStatus {
     variable_name: String,
+    category: VariableCategory,
     variable_id: Option<String>,
+    /// `None` if the variable does not exist.
+    attributes: Option<VarAttributes>,
 }
 
 impl TerraformVariableStatus {
@@ -16,80 +26,198 @@ impl TerraformVariableStatus {
         &self.variable_name
     }
 
+    pub fn get_category(&self) -> VariableCategory {
+        self.category
+    }
+
     pub fn get_variable_id(&self) -> &Option<String> {
         &self.variable_id
     }
+
+    pub fn get_attributes(&self) -> &Option<VarAttributes> {
+        &self.attributes
+    }
 }
 
-/// Checks specified variables already exist or not.
-pub async fn check_variable_status(
-    workspace_id: &str,
-    api_conn_prop: &TerraformApiConnectionProperty,
-    target_variable_names: &Vec<String>,
-) -> Result<Vec<TerraformVariableStatus>, Box<dyn std::error::Error>> {
-    let mut url = api_conn_prop.base_url().clone();
-    let token = api_conn_prop.token();
-
-    log::info!("Processing Workspace: {}", workspace_id);
-
-    let path = format!("/api/v2/workspaces/{}/vars", workspace_id);
-    url.set_path(&path);
-
-    let response = reqwest::Client::new()
-        .get(url.as_str())
-        .header("Authorization", format!("Bearer {}", token))
-        .header("Content-Type", "application/vnd.api+json")
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    let mut existing_variables = HashMap::new();
-    let response_json_value: serde_json::Value = serde_json::from_str(&response)?;
-    response_json_value["data"]
-        .as_array()
-        .unwrap()
-        .into_iter()
-        .for_each(|val| {
-            existing_variables.insert(
-                val["attributes"]["key"].as_str().unwrap().to_string(),
-                val["id"].as_str().unwrap().to_string(),
-            );
-        });
+impl TerraformClient {
+    /// Get every variable of a workspace or a variable set.
+    ///
+    /// Every page of the variables is read so that existing variables are never missed.
+    pub async fn get_variables(
+        &self,
+        target: &VariableTarget,
+    ) -> Result<Vec<Resource<VarAttributes>>, TfvarExportError> {
+        self.get_all_pages::<VarAttributes>(&target.vars_path())
+            .await
+    }
+}
+
+/// Checks specified variables exist in `existing_variables` or not.
+///
+/// A variable is identified by its name and category, as a workspace or a variable set can have a
+/// Terraform variable and an environment variable of the same name.
+pub fn variable_status(
+    existing_variables: &[Resource<VarAttributes>],
+    target_variables: &[(String, VariableCategory)],
+) -> Vec<TerraformVariableStatus> {
+    let mut existing_variables_map = HashMap::new();
+    for val in existing_variables.iter() {
+        existing_variables_map.insert(
+            (
+                val.get_attributes().get_key().to_string(),
+                val.get_attributes().get_category(),
+            ),
+            (val.get_id().to_string(), val.get_attributes().to_owned()),
+        );
+    }
 
     let mut result: Vec<TerraformVariableStatus> = Vec::new();
-    target_variable_names
-        .iter()
-        .for_each(|val_name| match existing_variables.get(val_name) {
-            Some(val_id) => result.push(TerraformVariableStatus {
+    target_variables.iter().for_each(|(val_name, category)| {
+        match existing_variables_map.get(&(val_name.to_owned(), *category)) {
+            Some((val_id, attributes)) => result.push(TerraformVariableStatus {
                 variable_name: val_name.to_owned(),
+                category: *category,
                 variable_id: Some(val_id.to_owned()),
+                attributes: Some(attributes.to_owned()),
             }),
             None => result.push(TerraformVariableStatus {
                 variable_name: val_name.to_owned(),
+                category: *category,
                 variable_id: None,
+                attributes: None,
             }),
-        });
+        }
+    });
 
-    log::info!("Variable status: {:#?}", result);
+    result.iter().for_each(|val| {
+        log::debug!(
+            "Variable status: {} ({}): {:?}",
+            val.variable_name,
+            val.category,
+            val.variable_id
+        )
+    });
 
-    Ok(result)
+    result
 }
 
 #[cfg(test)]
 mod tests {
     use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock,
+        MockServer,
+        ResponseTemplate,
+    };
 
     use super::*;
-    use crate::terraform_api::register_variable::{
-        create_variable,
-        tests::delete_variable,
-        TerraformVariableProperty,
+    use crate::terraform_api::{
+        connection_prop::TerraformApiConnectionProperty,
+        register_variable::TerraformVariableProperty,
     };
 
+    #[tokio::test]
+    async fn test_variable_status_multiple_pages() {
+        let server = MockServer::start().await;
+        for page in 1..=2 {
+            let next_page = if page < 2 {
+                json!(page + 1)
+            } else {
+                json!(null)
+            };
+            Mock::given(method("GET"))
+                .and(path("/api/v2/workspaces/ws-1/vars"))
+                .and(query_param("page[number]", page.to_string()))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "data": [{
+                        "id": format!("var-{}", page),
+                        "type": "vars",
+                        "attributes": {
+                            "key": format!("key_{}", page),
+                            "value": if page == 1 { json!("[\"a\"]") } else { json!(null) },
+                            "description": null,
+                            "category": if page == 1 { "terraform" } else { "env" },
+                            "hcl": page == 1,
+                            "sensitive": page == 2
+                        }
+                    }],
+                    "meta": {"pagination": {"current-page": page, "next-page": next_page}}
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap();
+        let existing_variables = client
+            .get_variables(&VariableTarget::Workspace(String::from("ws-1")))
+            .await
+            .unwrap();
+        let res = variable_status(&existing_variables, &[
+            (String::from("key_1"), VariableCategory::Terraform),
+            (String::from("key_2"), VariableCategory::Env),
+            (String::from("key_3"), VariableCategory::Terraform),
+            // Existing but of another category
+            (String::from("key_1"), VariableCategory::Env),
+        ]);
+
+        assert_eq!(res, vec![
+            TerraformVariableStatus {
+                variable_name: String::from("key_1"),
+                category: VariableCategory::Terraform,
+                variable_id: Some(String::from("var-1")),
+                attributes: Some(
+                    serde_json::from_value(json!({
+                        "key": "key_1",
+                        "value": "[\"a\"]",
+                        "description": null,
+                        "category": "terraform",
+                        "hcl": true,
+                        "sensitive": false
+                    }))
+                    .unwrap(),
+                ),
+            },
+            // Only found on the second page
+            TerraformVariableStatus {
+                variable_name: String::from("key_2"),
+                category: VariableCategory::Env,
+                variable_id: Some(String::from("var-2")),
+                attributes: Some(
+                    serde_json::from_value(json!({
+                        "key": "key_2",
+                        "value": null,
+                        "description": null,
+                        "category": "env",
+                        "hcl": false,
+                        "sensitive": true
+                    }))
+                    .unwrap(),
+                ),
+            },
+            TerraformVariableStatus {
+                variable_name: String::from("key_3"),
+                category: VariableCategory::Terraform,
+                variable_id: None,
+                attributes: None,
+            },
+            TerraformVariableStatus {
+                variable_name: String::from("key_1"),
+                category: VariableCategory::Env,
+                variable_id: None,
+                attributes: None,
+            },
+        ]);
+    }
+
     #[tokio::test]
     #[ignore = "Requires API access"]
-    async fn test_check_variable_status() {
+    async fn test_variable_status() {
         // Should NOT exist
         let test_val_1 = uuid::Uuid::new_v4().to_string();
         // Should exist
@@ -101,52 +229,61 @@ mod tests {
         // Should NOT exist
         let test_val_5 = uuid::Uuid::new_v4().to_string();
 
-        let api_conn_prop = TerraformApiConnectionProperty::new(
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
             url::Url::parse("https://app.terraform.io").unwrap(),
             std::env::var("TFVE_TOKEN").unwrap(),
-        );
+        ))
+        .unwrap();
         let workspace_id = &std::env::var("TFVE_WORKSPACE_ID_TESTING")
             .expect("Environment variable `TFVE_WORKSPACE_ID_TESTING` required.");
 
-        let create_result = create_variable(workspace_id, &api_conn_prop, &vec![
-            TerraformVariableProperty::new(
-                None,
+        let create_result = client
+            .create_variable(&VariableTarget::Workspace(workspace_id.to_string()), &[
+                TerraformVariableProperty::new(
+                    None,
+                    test_val_2.clone(),
+                    Some(test_val_2.clone()),
+                    json!(test_val_2),
+                ),
+                TerraformVariableProperty::new(
+                    None,
+                    test_val_4.clone(),
+                    Some(test_val_4.clone()),
+                    json!(test_val_4),
+                ),
+            ])
+            .await
+            .unwrap();
+
+        let existing_variables = client
+            .get_variables(&VariableTarget::Workspace(workspace_id.to_string()))
+            .await
+            .unwrap();
+        let res = variable_status(
+            &existing_variables,
+            &[
+                test_val_1.clone(),
                 test_val_2.clone(),
-                Some(test_val_2.clone()),
-                json!(test_val_2),
-            ),
-            TerraformVariableProperty::new(
-                None,
+                test_val_3.clone(),
                 test_val_4.clone(),
-                Some(test_val_4.clone()),
-                json!(test_val_4),
-            ),
-        ])
-        .await
-        .unwrap();
-
-        let res = check_variable_status(workspace_id, &api_conn_prop, &vec![
-            test_val_1.clone(),
-            test_val_2.clone(),
-            test_val_3.clone(),
-            test_val_4.clone(),
-            test_val_5.clone(),
-        ])
-        .await
-        .unwrap();
+                test_val_5.clone(),
+            ]
+            .map(|val| (val, VariableCategory::Terraform)),
+        );
 
-        assert!(res.get(0).unwrap().get_variable_id().is_none());
+        assert!(res.first().unwrap().get_variable_id().is_none());
         assert!(res.get(1).unwrap().get_variable_id().is_some());
         assert!(res.get(2).unwrap().get_variable_id().is_none());
         assert!(res.get(3).unwrap().get_variable_id().is_some());
         assert!(res.get(4).unwrap().get_variable_id().is_none());
 
         // Delete test data
-        let ids = create_result
+        let ids: Vec<String> = create_result
             .iter()
             .map(|val| val.get_variable_id().to_owned())
             .collect();
-        delete_variable(&api_conn_prop, &ids, workspace_id)
+        client
+            .delete_variable(&VariableTarget::Workspace(workspace_id.to_string()), &ids)
             .await
             .unwrap();
     }
@@ -0,0 +1,476 @@
+//! Reusable client of HCP Terraform API.
+//!
+//! Workspace, project, variable set and variable operations are implemented as methods of
+//! [`TerraformClient`] in their own modules.
+
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+
+use crate::{
+    error::TfvarExportError,
+    terraform_api::{
+        connection_prop::TerraformApiConnectionProperty,
+        rate_limiter::{RateLimiter, DEFAULT_REQUESTS_PER_SECOND},
+        retry::RetryPolicy,
+    },
+};
+
+/// User-Agent sent with every request
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Client of HCP Terraform API
+///
+/// Holds one pooled `reqwest::Client` with the default headers and one rate limiter, so cloning is
+/// cheap and clones share the connection pool and the rate limit.
+#[derive(Debug, Clone)]
+pub struct TerraformClient {
+    base_url: url::Url,
+    http_client: reqwest::Client,
+    retry_policy: RetryPolicy,
+    rate_limiter: RateLimiter,
+    #[cfg(test)]
+    cassette: Option<std::sync::Arc<crate::terraform_api::cassette::Cassette>>,
+}
+
+impl TerraformClient {
+    pub fn new(api_conn_prop: &TerraformApiConnectionProperty) -> Result<Self, TfvarExportError> {
+        let mut authorization = HeaderValue::from_str(&format!("Bearer {}", api_conn_prop.token()))
+            .map_err(|_| {
+                TfvarExportError::Config(String::from("`TFVE_TOKEN` contains invalid characters."))
+            })?;
+        authorization.set_sensitive(true);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, authorization);
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/vnd.api+json"),
+        );
+
+        let http_client = reqwest::Client::builder()
+            .default_headers(headers)
+            .user_agent(USER_AGENT)
+            .connect_timeout(api_conn_prop.connect_timeout())
+            .timeout(api_conn_prop.request_timeout())
+            .build()?;
+
+        Ok(Self {
+            base_url: api_conn_prop.base_url().clone(),
+            http_client,
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: RateLimiter::new(DEFAULT_REQUESTS_PER_SECOND)?,
+            #[cfg(test)]
+            cassette: None,
+        })
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Record requests to or replay them from `cassette`.
+    #[cfg(test)]
+    pub fn with_cassette(
+        mut self,
+        cassette: std::sync::Arc<crate::terraform_api::cassette::Cassette>,
+    ) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Return the URL of `path` on the API host.
+    pub fn url(&self, path: &str) -> url::Url {
+        let mut url = self.base_url.clone();
+        url.set_path(path);
+        url
+    }
+
+    /// Send a request and return the response if its status is `expected_status`.
+    ///
+    /// Every attempt waits for the shared rate limiter. `429` responses and failures to connect,
+    /// and `5xx` responses and timeouts of requests other than `POST`, are retried according to the
+    /// retry policy.
+    pub async fn send(
+        &self,
+        method: reqwest::Method,
+        url: url::Url,
+        body: Option<&serde_json::Value>,
+        expected_status: reqwest::StatusCode,
+    ) -> Result<reqwest::Response, TfvarExportError> {
+        let mut attempt = 1;
+        loop {
+            self.rate_limiter.wait().await;
+
+            let response = match self.execute(&method, &url, body).await {
+                Ok(response) => response,
+                Err(TfvarExportError::Http(err))
+                    if RetryPolicy::is_retryable_error(&method, &err)
+                        && attempt < self.retry_policy.max_attempts() =>
+                {
+                    let delay = self.retry_policy.delay(attempt, &HeaderMap::new());
+                    log::warn!(
+                        "`{} {}` failed with {}, retrying in {:?} (attempt {}/{}).",
+                        method,
+                        url.path(),
+                        err,
+                        delay,
+                        attempt + 1,
+                        self.retry_policy.max_attempts()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            let status = response.status();
+            if status == expected_status {
+                return Ok(response);
+            }
+
+            if !RetryPolicy::is_retryable(&method, status)
+                || self.retry_policy.max_attempts() <= attempt
+            {
+                return Err(
+                    TfvarExportError::from_response(method.as_str(), url.path(), response).await,
+                );
+            }
+
+            let delay = self.retry_policy.delay(attempt, response.headers());
+            log::warn!(
+                "`{} {}` responded with {}, retrying in {:?} (attempt {}/{}).",
+                method,
+                url.path(),
+                status,
+                delay,
+                attempt + 1,
+                self.retry_policy.max_attempts()
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Send a request once.
+    async fn execute(
+        &self,
+        method: &reqwest::Method,
+        url: &url::Url,
+        body: Option<&serde_json::Value>,
+    ) -> Result<reqwest::Response, TfvarExportError> {
+        #[cfg(test)]
+        if let Some(cassette) = &self.cassette {
+            if cassette.is_replay() {
+                return Ok(cassette.replay(method, url, body));
+            }
+        }
+
+        let mut request = self.http_client.request(method.clone(), url.clone());
+        if let Some(body) = body {
+            request = request.body(body.to_string());
+        }
+        let response = request.send().await?;
+
+        #[cfg(test)]
+        if let Some(cassette) = &self.cassette {
+            return cassette.record(method, url, body, response).await;
+        }
+
+        Ok(response)
+    }
+
+    /// Send a request and deserialize the response body.
+    pub async fn send_json<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        url: url::Url,
+        body: Option<&serde_json::Value>,
+        expected_status: reqwest::StatusCode,
+    ) -> Result<T, TfvarExportError> {
+        let path = url.path().to_string();
+        let response = self
+            .send(method.clone(), url, body, expected_status)
+            .await?;
+        let response_val = serde_json::from_str(&response.text().await?).map_err(|err| {
+            TfvarExportError::Parse(format!(
+                "Unexpected response from `{} {}`: {}",
+                method, path, err
+            ))
+        })?;
+
+        Ok(response_val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_json, header, method, path},
+        Mock,
+        MockServer,
+        ResponseTemplate,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_json_headers() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/ping"))
+            .and(header("Authorization", "Bearer token"))
+            .and(header("Content-Type", "application/vnd.api+json"))
+            .and(header("User-Agent", USER_AGENT))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"data": []})))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap();
+
+        // The pooled client is reused across requests and clones.
+        for client in [client.clone(), client] {
+            let res: serde_json::Value = client
+                .send_json(
+                    reqwest::Method::GET,
+                    client.url("/api/v2/ping"),
+                    None,
+                    reqwest::StatusCode::OK,
+                )
+                .await
+                .unwrap();
+            assert_eq!(res, json!({"data": []}));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_retry() {
+        let server = MockServer::start().await;
+        // Rate limited twice, then succeeds
+        Mock::given(method("POST"))
+            .and(path("/api/v2/rate-limited"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v2/rate-limited"))
+            .and(body_json(json!({"data": "payload"})))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({"data": "created"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // Always fails
+        Mock::given(method("GET"))
+            .and(path("/api/v2/unavailable"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(3)
+            .mount(&server)
+            .await;
+        // Not retried as the resource may have been created
+        Mock::given(method("POST"))
+            .and(path("/api/v2/unavailable"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // Never retried
+        Mock::given(method("GET"))
+            .and(path("/api/v2/not-found"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap()
+        .with_retry_policy(
+            RetryPolicy::new(3).with_base_delay(std::time::Duration::from_millis(1)),
+        );
+
+        let res: serde_json::Value = client
+            .send_json(
+                reqwest::Method::POST,
+                client.url("/api/v2/rate-limited"),
+                Some(&json!({"data": "payload"})),
+                reqwest::StatusCode::CREATED,
+            )
+            .await
+            .unwrap();
+        assert_eq!(res, json!({"data": "created"}));
+
+        let res = client
+            .send(
+                reqwest::Method::GET,
+                client.url("/api/v2/unavailable"),
+                None,
+                reqwest::StatusCode::OK,
+            )
+            .await;
+        assert!(matches!(
+            res,
+            Err(TfvarExportError::Api { status: 503, .. })
+        ));
+
+        let res = client
+            .send(
+                reqwest::Method::POST,
+                client.url("/api/v2/unavailable"),
+                Some(&json!({"data": "payload"})),
+                reqwest::StatusCode::CREATED,
+            )
+            .await;
+        assert!(matches!(
+            res,
+            Err(TfvarExportError::Api { status: 503, .. })
+        ));
+
+        let res = client
+            .send(
+                reqwest::Method::GET,
+                client.url("/api/v2/not-found"),
+                None,
+                reqwest::StatusCode::OK,
+            )
+            .await;
+        assert!(matches!(
+            res,
+            Err(TfvarExportError::Api { status: 404, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_request_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/slow"))
+            .respond_with(
+                ResponseTemplate::new(200).set_delay(std::time::Duration::from_millis(500)),
+            )
+            .mount(&server)
+            .await;
+
+        let client = TerraformClient::new(
+            &TerraformApiConnectionProperty::new(
+                url::Url::parse(&server.uri()).unwrap(),
+                String::from("token"),
+            )
+            .with_request_timeout(std::time::Duration::from_millis(50)),
+        )
+        .unwrap()
+        .with_retry_policy(
+            RetryPolicy::new(2).with_base_delay(std::time::Duration::from_millis(1)),
+        );
+
+        let res = client
+            .send(
+                reqwest::Method::GET,
+                client.url("/api/v2/slow"),
+                None,
+                reqwest::StatusCode::OK,
+            )
+            .await;
+        assert!(matches!(res, Err(TfvarExportError::Http(err)) if err.is_timeout()));
+    }
+
+    #[tokio::test]
+    async fn test_send_retry_transport_error() {
+        let server = MockServer::start().await;
+        // Times out once, then succeeds
+        Mock::given(method("GET"))
+            .and(path("/api/v2/slow"))
+            .respond_with(
+                ResponseTemplate::new(200).set_delay(std::time::Duration::from_millis(500)),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/slow"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"data": []})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // Not retried as the resource may have been created
+        Mock::given(method("POST"))
+            .and(path("/api/v2/slow"))
+            .respond_with(
+                ResponseTemplate::new(201).set_delay(std::time::Duration::from_millis(500)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = TerraformClient::new(
+            &TerraformApiConnectionProperty::new(
+                url::Url::parse(&server.uri()).unwrap(),
+                String::from("token"),
+            )
+            .with_request_timeout(std::time::Duration::from_millis(50)),
+        )
+        .unwrap()
+        .with_retry_policy(
+            RetryPolicy::new(3).with_base_delay(std::time::Duration::from_millis(1)),
+        );
+
+        let res: serde_json::Value = client
+            .send_json(
+                reqwest::Method::GET,
+                client.url("/api/v2/slow"),
+                None,
+                reqwest::StatusCode::OK,
+            )
+            .await
+            .unwrap();
+        assert_eq!(res, json!({"data": []}));
+
+        let res = client
+            .send(
+                reqwest::Method::POST,
+                client.url("/api/v2/slow"),
+                Some(&json!({"data": "payload"})),
+                reqwest::StatusCode::CREATED,
+            )
+            .await;
+        assert!(matches!(res, Err(TfvarExportError::Http(err)) if err.is_timeout()));
+
+        // Nothing listens on a released port.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&format!("http://127.0.0.1:{}", port)).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap()
+        .with_retry_policy(
+            RetryPolicy::new(2).with_base_delay(std::time::Duration::from_millis(1)),
+        );
+        let res = client
+            .send(
+                reqwest::Method::POST,
+                client.url("/api/v2/slow"),
+                Some(&json!({"data": "payload"})),
+                reqwest::StatusCode::CREATED,
+            )
+            .await;
+        assert!(matches!(res, Err(TfvarExportError::Http(err)) if err.is_connect()));
+    }
+}
@@ -1,3 +1,9 @@
+/// Default timeout for establishing a connection
+const DEFAULT_CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Default timeout for a whole request
+const DEFAULT_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
 /// Properties for API connection
 ///
 /// Official Doc: [HCP Terraform API Documentation](https://developer.hashicorp.com/terraform/cloud-docs/api-docs)
@@ -6,11 +12,30 @@ pub struct TerraformApiConnectionProperty {
     base_url: url::Url,
     /// Authorization token
     token: String,
+    /// Timeout for establishing a connection
+    connect_timeout: std::time::Duration,
+    /// Timeout for a whole request
+    request_timeout: std::time::Duration,
 }
 
 impl TerraformApiConnectionProperty {
     pub fn new(base_url: url::Url, token: String) -> Self {
-        Self { base_url, token }
+        Self {
+            base_url,
+            token,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout: std::time::Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    pub fn with_request_timeout(mut self, request_timeout: std::time::Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
     }
 
     pub fn base_url(&self) -> &url::Url {
@@ -20,4 +45,12 @@ impl TerraformApiConnectionProperty {
     pub fn token(&self) -> &str {
         &self.token
     }
+
+    pub fn connect_timeout(&self) -> std::time::Duration {
+        self.connect_timeout
+    }
+
+    pub fn request_timeout(&self) -> std::time::Duration {
+        self.request_timeout
+    }
 }
@@ -1,10 +1,16 @@
-//! Get a list of Terraform Cloud workspaces.
+//! Get Terraform Cloud workspaces.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
 
-use crate::terraform_api::connection_prop::TerraformApiConnectionProperty;
+use crate::{
+    error::TfvarExportError,
+    terraform_api::{
+        client::TerraformClient,
+        models::{Document, ProjectAttributes, Resource, WorkspaceAttributes},
+    },
+};
 
 /// Terraform Project info
 #[derive(Debug, Serialize, Deserialize)]
@@ -18,7 +24,9 @@ struct TerraformProject {
 pub struct TerraformWorkspace {
     terraform_workspace_id: String,
     terraform_workspace_name: String,
-    terraform_project: TerraformProject,
+    /// `None` if the workspace has no project relationship or it was got alone with
+    /// [`TerraformClient::get_workspace`].
+    terraform_project: Option<TerraformProject>,
 }
 
 impl TerraformWorkspace {
@@ -29,134 +37,509 @@ impl TerraformWorkspace {
     pub fn get_workspace_name(&self) -> &str {
         &self.terraform_workspace_name
     }
+
+    /// `None` if the workspace has no project relationship or it is not resolved.
+    pub fn get_project_name(&self) -> Option<&str> {
+        self.terraform_project
+            .as_ref()
+            .map(|val| val.terraform_project_name.as_str())
+    }
 }
 
-/// Max element numbers per page.
-///
-/// ## TODO:
-///
-/// If your case exceeds this, additional implementations are required.
-///
-/// ## Reference
-///
-/// https://developer.hashicorp.com/terraform/cloud-docs/api-docs/projects#list-projects
-const TERRAFORM_API_QS_PAGE_SIZE: u8 = 100;
-
-/// Get Terraform projects and return a HashMap of `Project ID: Project Name`.
-pub async fn get_projects(
-    organization_name: &str,
-    api_conn_prop: &TerraformApiConnectionProperty,
-) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
-    let mut url = api_conn_prop.base_url().clone();
-    let token = api_conn_prop.token();
-
-    let path = format!("/api/v2/organizations/{}/projects", organization_name);
-    url.set_path(&path);
-
-    log::info!(
-        "Getting project(s) from the organization {}.",
-        organization_name
-    );
-
-    let response_projects = reqwest::Client::new()
-        .get(url.as_str())
-        .header("Authorization", format!("Bearer {}", token))
-        .header("Content-Type", "application/vnd.api+json")
-        .query(&[("page[size]", TERRAFORM_API_QS_PAGE_SIZE)])
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    let mut result = HashMap::new();
-    let response_projects_val: serde_json::Value = serde_json::from_str(&response_projects)?;
-    response_projects_val["data"]
-        .as_array()
-        .unwrap()
-        .into_iter()
-        .for_each(|val| {
-            let terraform_project_id = val["id"].as_str().unwrap().to_string();
-            let terraform_project_name = val["attributes"]["name"].as_str().unwrap().to_string();
-
-            result.insert(terraform_project_id, terraform_project_name);
-        });
-
-    log::info!("{} project(s) found.", result.len());
-
-    Ok(result)
+/// Whether `value` is a workspace ID such as `ws-xxxxxxxxxxxxxxxx` rather than a name.
+pub fn is_workspace_id(value: &str) -> bool {
+    match value.strip_prefix("ws-") {
+        Some(val) => val.len() == 16 && val.chars().all(|c| c.is_ascii_alphanumeric()),
+        None => false,
+    }
 }
 
-/// Get Terraform workspaces and return vector of `TerraformWorkspace` struct.
-///
-/// Using `--show-workspaces` flag prints workspaces with their associated projects.
-pub async fn get_workspaces(
-    show_workspaces: bool,
-    organization_name: &str,
-    api_conn_prop: &TerraformApiConnectionProperty,
-) -> Result<Vec<TerraformWorkspace>, Box<dyn std::error::Error>> {
-    let mut url = api_conn_prop.base_url().clone();
-    let token = api_conn_prop.token();
-
-    let path = format!("/api/v2/organizations/{}/workspaces", organization_name);
-    url.set_path(&path);
-
-    log::info!(
-        "Getting workspace(s) from the organization {}.",
-        organization_name
-    );
-
-    let response_workspaces = reqwest::Client::new()
-        .get(url.as_str())
-        .header("Authorization", format!("Bearer {}", token))
-        .header("Content-Type", "application/vnd.api+json")
-        .query(&[("page[size]", TERRAFORM_API_QS_PAGE_SIZE)])
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    // List workspaces and then get workspaces to map a workspace and its project.
-    let response_workspaces_val: serde_json::Value = serde_json::from_str(&response_workspaces)?;
-    let mut terraform_workspaces = Vec::new();
-    let terraform_projects_map = get_projects(organization_name, api_conn_prop).await?;
-    response_workspaces_val["data"]
-        .as_array()
-        .unwrap()
-        .into_iter()
-        .for_each(|val| {
-            let terraform_workspace_id = val["id"].as_str().unwrap().to_string();
-            let terraform_workspace_name = val["attributes"]["name"].as_str().unwrap().to_string();
-            let terraform_project_id = val["relationships"]["project"]["data"]["id"]
-                .as_str()
-                .unwrap()
-                .to_string();
+impl TerraformClient {
+    /// Get Terraform projects and return a HashMap of `Project ID: Project Name`.
+    pub async fn get_projects(
+        &self,
+        organization_name: &str,
+    ) -> Result<HashMap<String, String>, TfvarExportError> {
+        let path = format!("/api/v2/organizations/{}/projects", organization_name);
+
+        log::info!(
+            "Getting project(s) from the organization {}.",
+            organization_name
+        );
+
+        let mut result = HashMap::new();
+        for val in self.get_all_pages::<ProjectAttributes>(&path).await? {
+            result.insert(
+                val.get_id().to_string(),
+                val.get_attributes().get_name().to_string(),
+            );
+        }
+
+        log::info!("{} project(s) found.", result.len());
+
+        Ok(result)
+    }
+
+    /// Get a workspace by its ID, or by its name in the organization, without listing the
+    /// organization.
+    ///
+    /// Return `None` if the workspace does not exist. The project of the workspace is not resolved.
+    pub async fn get_workspace(
+        &self,
+        organization_name: Option<&str>,
+        workspace: &str,
+    ) -> Result<Option<TerraformWorkspace>, TfvarExportError> {
+        let path = if is_workspace_id(workspace) {
+            format!("/api/v2/workspaces/{}", workspace)
+        } else {
+            let organization_name = organization_name.ok_or_else(|| {
+                TfvarExportError::Config(format!(
+                    "Environment variable `TFVE_ORGANIZATION_NAME` is required to get the \
+                     workspace `{}` by name.",
+                    workspace
+                ))
+            })?;
+            format!(
+                "/api/v2/organizations/{}/workspaces/{}",
+                organization_name, workspace
+            )
+        };
+
+        let document: Document<Resource<WorkspaceAttributes>> = match self
+            .send_json(
+                reqwest::Method::GET,
+                self.url(&path),
+                None,
+                reqwest::StatusCode::OK,
+            )
+            .await
+        {
+            Ok(val) => val,
+            Err(TfvarExportError::Api { status: 404, .. }) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let resource = document.into_data();
+
+        Ok(Some(TerraformWorkspace {
+            terraform_workspace_id: resource.get_id().to_string(),
+            terraform_workspace_name: resource.get_attributes().get_name().to_string(),
+            terraform_project: None,
+        }))
+    }
+
+    /// Get the IDs of the workspaces having all of `tags`.
+    ///
+    /// The workspaces are filtered by the API; `key=value` tags with `filter[tagged][i][key]` and
+    /// `filter[tagged][i][value]`, and the other tags with `search[tags]`. Tags such as `env:prod`
+    /// are plain tags, as a colon is allowed in their names.
+    pub async fn get_tagged_workspace_ids(
+        &self,
+        organization_name: &str,
+        tags: &[String],
+    ) -> Result<HashSet<String>, TfvarExportError> {
+        let path = format!("/api/v2/organizations/{}/workspaces", organization_name);
+
+        log::info!(
+            "Getting workspace(s) tagged with {} from the organization {}.",
+            tags.join(", "),
+            organization_name
+        );
+
+        let (key_value_tags, tag_names): (Vec<&String>, Vec<&String>) =
+            tags.iter().partition(|val| val.contains('='));
+        let mut query: Vec<(String, String)> = Vec::new();
+        for (i, tag) in key_value_tags.iter().enumerate() {
+            let (key, value) = tag.split_once('=').unwrap();
+            query.push((format!("filter[tagged][{}][key]", i), key.to_string()));
+            query.push((format!("filter[tagged][{}][value]", i), value.to_string()));
+        }
+        if !tag_names.is_empty() {
+            let tag_names: Vec<&str> = tag_names.iter().map(|val| val.as_str()).collect();
+            query.push((String::from("search[tags]"), tag_names.join(",")));
+        }
+        let query: Vec<(&str, &str)> = query
+            .iter()
+            .map(|(key, val)| (key.as_str(), val.as_str()))
+            .collect();
+
+        let result: HashSet<String> = self
+            .get_all_pages_with_query::<WorkspaceAttributes>(&path, &query)
+            .await?
+            .iter()
+            .map(|val| val.get_id().to_string())
+            .collect();
+
+        log::info!("{} workspace(s) found.", result.len());
+
+        Ok(result)
+    }
+
+    /// Get Terraform workspaces and return vector of `TerraformWorkspace` struct.
+    ///
+    /// Using `--show-workspaces` flag prints workspaces with their associated projects.
+    pub async fn get_workspaces(
+        &self,
+        show_workspaces: bool,
+        organization_name: &str,
+    ) -> Result<Vec<TerraformWorkspace>, TfvarExportError> {
+        let path = format!("/api/v2/organizations/{}/workspaces", organization_name);
+
+        log::info!(
+            "Getting workspace(s) from the organization {}.",
+            organization_name
+        );
+
+        // List workspaces and then get workspaces to map a workspace and its project.
+        let response_workspaces = self.get_all_pages::<WorkspaceAttributes>(&path).await?;
+        let mut terraform_workspaces = Vec::new();
+        let terraform_projects_map = self.get_projects(organization_name).await?;
+        for val in response_workspaces.iter() {
+            let terraform_workspace_name = val.get_attributes().get_name().to_string();
+            let terraform_project = match val.get_related_id("project") {
+                Some(terraform_project_id) => {
+                    let terraform_project_name = terraform_projects_map
+                        .get(terraform_project_id)
+                        .ok_or_else(|| {
+                            TfvarExportError::Parse(format!(
+                                "Project `{}` of the workspace `{}` was not found.",
+                                terraform_project_id, terraform_workspace_name
+                            ))
+                        })?
+                        .to_string();
+                    Some(TerraformProject {
+                        terraform_project_id: terraform_project_id.to_string(),
+                        terraform_project_name,
+                    })
+                },
+                None => None,
+            };
 
             terraform_workspaces.push(TerraformWorkspace {
-                terraform_workspace_id,
+                terraform_workspace_id: val.get_id().to_string(),
                 terraform_workspace_name,
-                terraform_project: TerraformProject {
-                    terraform_project_id: terraform_project_id.clone(),
-                    terraform_project_name: terraform_projects_map
-                        .get(&terraform_project_id)
-                        .unwrap()
-                        .to_string(),
-                },
+                terraform_project,
             })
-        });
+        }
 
-    log::info!("{} workspace(s) found.", terraform_workspaces.len());
+        log::info!("{} workspace(s) found.", terraform_workspaces.len());
 
-    if show_workspaces {
-        println!("{}", serde_json::to_string_pretty(&terraform_workspaces)?)
-    }
+        if show_workspaces {
+            println!("{}", serde_json::to_string_pretty(&terraform_workspaces)?)
+        }
 
-    Ok(terraform_workspaces)
+        Ok(terraform_workspaces)
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
 
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param, query_param_is_missing},
+        Mock,
+        MockServer,
+        ResponseTemplate,
+    };
+
     use super::*;
+    use crate::terraform_api::{
+        cassette::Cassette,
+        connection_prop::TerraformApiConnectionProperty,
+        mock_server::{MockTerraformServer, MOCK_TOKEN},
+    };
+
+    /// Mount one page of a list endpoint which links to the next page via `links.next`.
+    async fn mount_page(
+        server: &MockServer,
+        list_path: &str,
+        page: usize,
+        total_pages: usize,
+        data: Vec<serde_json::Value>,
+    ) {
+        let next = if page < total_pages {
+            json!(format!(
+                "{}{}?page%5Bnumber%5D={}&page%5Bsize%5D=100",
+                server.uri(),
+                list_path,
+                page + 1
+            ))
+        } else {
+            json!(null)
+        };
+        Mock::given(method("GET"))
+            .and(path(list_path))
+            .and(query_param("page[number]", page.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": data,
+                "links": {"next": next},
+                "meta": {"pagination": {"current-page": page, "total-pages": total_pages}}
+            })))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_workspaces_multiple_pages() {
+        const WORKSPACES_PER_PAGE: usize = 100;
+        const WORKSPACE_PAGES: usize = 3;
+        const PROJECT_PAGES: usize = 2;
+
+        let server = MockServer::start().await;
+
+        for page in 1..=PROJECT_PAGES {
+            mount_page(
+                &server,
+                "/api/v2/organizations/org/projects",
+                page,
+                PROJECT_PAGES,
+                vec![json!({
+                    "id": format!("prj-{}", page),
+                    "type": "projects",
+                    "attributes": {"name": format!("project-{}", page)}
+                })],
+            )
+            .await;
+        }
+        for page in 1..=WORKSPACE_PAGES {
+            let data = (0..WORKSPACES_PER_PAGE)
+                .map(|i| {
+                    let n = (page - 1) * WORKSPACES_PER_PAGE + i;
+                    json!({
+                        "id": format!("ws-{}", n),
+                        "type": "workspaces",
+                        "attributes": {"name": format!("workspace-{}", n)},
+                        "relationships": {
+                            "project": {"data": {"id": format!("prj-{}", n % PROJECT_PAGES + 1), "type": "projects"}}
+                        }
+                    })
+                })
+                .collect();
+            mount_page(
+                &server,
+                "/api/v2/organizations/org/workspaces",
+                page,
+                WORKSPACE_PAGES,
+                data,
+            )
+            .await;
+        }
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap();
+
+        let resp = client.get_workspaces(false, "org").await.unwrap();
+        assert_eq!(resp.len(), WORKSPACES_PER_PAGE * WORKSPACE_PAGES);
+        // A workspace on the last page
+        let last = resp.last().unwrap();
+        assert_eq!(last.get_workspace_id(), "ws-299");
+        assert_eq!(last.get_workspace_name(), "workspace-299");
+        assert_eq!(
+            last.terraform_project
+                .as_ref()
+                .unwrap()
+                .terraform_project_name,
+            "project-2"
+        );
+        // Projects on every page are mapped
+        assert_eq!(
+            resp.first()
+                .unwrap()
+                .terraform_project
+                .as_ref()
+                .unwrap()
+                .terraform_project_name,
+            "project-1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_workspaces_without_project() {
+        let server = MockServer::start().await;
+        mount_page(&server, "/api/v2/organizations/org/projects", 1, 1, vec![]).await;
+        mount_page(&server, "/api/v2/organizations/org/workspaces", 1, 1, vec![
+            json!({
+                "id": "ws-1",
+                "type": "workspaces",
+                "attributes": {"name": "workspace-1"},
+                "relationships": {"project": {"data": null}}
+            }),
+        ])
+        .await;
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap();
+
+        let resp = client.get_workspaces(false, "org").await.unwrap();
+        assert_eq!(resp.len(), 1);
+        assert!(resp.first().unwrap().terraform_project.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_workspaces_malformed() {
+        let server = MockServer::start().await;
+        mount_page(&server, "/api/v2/organizations/org/workspaces", 1, 1, vec![
+            json!({"id": "ws-1", "type": "workspaces", "attributes": {}}),
+        ])
+        .await;
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap();
+
+        let resp = client.get_workspaces(false, "org").await;
+        assert!(matches!(resp, Err(TfvarExportError::Parse(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_workspace_mock() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization("org");
+        let workspace_id = server.add_workspace("org", "workspace", None);
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from(MOCK_TOKEN),
+        ))
+        .unwrap();
+
+        // By ID without the organization
+        let workspace = client
+            .get_workspace(None, &workspace_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(workspace.get_workspace_name(), "workspace");
+
+        // By name
+        let workspace = client
+            .get_workspace(Some("org"), "workspace")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(workspace.get_workspace_id(), workspace_id);
+
+        // Not found
+        assert!(client
+            .get_workspace(Some("org"), "unknown")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(client
+            .get_workspace(None, "ws-0000000000000000")
+            .await
+            .unwrap()
+            .is_none());
+
+        // A name requires the organization
+        assert!(matches!(
+            client.get_workspace(None, "workspace").await,
+            Err(TfvarExportError::Config(_))
+        ));
+
+        // Only the workspaces were requested
+        assert_eq!(server.received_requests().await, vec![
+            format!("GET /api/v2/workspaces/{}", workspace_id),
+            String::from("GET /api/v2/organizations/org/workspaces/workspace"),
+            String::from("GET /api/v2/organizations/org/workspaces/unknown"),
+            String::from("GET /api/v2/workspaces/ws-0000000000000000"),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_get_tagged_workspace_ids_query() {
+        let server = MockServer::start().await;
+        // Key-value tags
+        Mock::given(method("GET"))
+            .and(path("/api/v2/organizations/org/workspaces"))
+            .and(query_param("filter[tagged][0][key]", "env"))
+            .and(query_param("filter[tagged][0][value]", "prod"))
+            .and(query_param("filter[tagged][1][key]", "layer"))
+            .and(query_param("filter[tagged][1][value]", "app"))
+            .and(query_param_is_missing("search[tags]"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [{"id": "ws-1", "type": "workspaces", "attributes": {"name": "app"}}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // Tag names, including one with a colon
+        Mock::given(method("GET"))
+            .and(path("/api/v2/organizations/org/workspaces"))
+            .and(query_param("search[tags]", "legacy,env:prod"))
+            .and(query_param_is_missing("filter[tagged][0][key]"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [{"id": "ws-2", "type": "workspaces", "attributes": {"name": "old"}}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap();
+
+        let tags = |tags: &[&str]| -> Vec<String> { tags.iter().map(|v| v.to_string()).collect() };
+        assert_eq!(
+            client
+                .get_tagged_workspace_ids("org", &tags(&["env=prod", "layer=app"]))
+                .await
+                .unwrap(),
+            HashSet::from([String::from("ws-1")])
+        );
+        assert_eq!(
+            client
+                .get_tagged_workspace_ids("org", &tags(&["legacy", "env:prod"]))
+                .await
+                .unwrap(),
+            HashSet::from([String::from("ws-2")])
+        );
+    }
+
+    #[test]
+    fn test_is_workspace_id() {
+        assert!(is_workspace_id("ws-aBcD1234eFgH5678"));
+        assert!(!is_workspace_id("ws-new"));
+        assert!(!is_workspace_id("ws-aBcD1234eFgH5678-old"));
+        assert!(!is_workspace_id("prj-aBcD1234eFgH5678"));
+    }
+
+    #[tokio::test]
+    #[ignore = "Requires the cassette recorded with `TFVE_CASSETTE_MODE=record`"]
+    async fn test_get_workspaces_cassette() {
+        let cassette = std::sync::Arc::new(Cassette::new("get_workspaces"));
+        let client = cassette.client();
+        let organization_name = cassette.env("TFVE_ORGANIZATION_NAME", "example-org");
+        let workspace_ids = [
+            cassette.env("TFVE_WORKSPACE_ID_TESTING", "ws-testing"),
+            cassette.env("TFVE_WORKSPACE_ID_TESTING2", "ws-testing2"),
+        ];
+
+        let resp = client
+            .get_workspaces(false, &organization_name)
+            .await
+            .unwrap();
+        for workspace_id in workspace_ids.iter() {
+            let workspace = resp
+                .iter()
+                .find(|val| val.get_workspace_id() == workspace_id)
+                .unwrap();
+            assert!(!workspace.get_workspace_name().is_empty());
+            assert!(workspace.terraform_project.is_some());
+        }
+
+        cassette.finish();
+    }
 
     #[tokio::test]
     #[ignore = "Requires API access, Expected value differs depending on the environment"]
@@ -164,14 +547,16 @@ pub mod tests {
         // TODO: Expected value differs depending on the environment.
         const WORKSPACE_NUMBERS: usize = 4;
 
-        let api_conn_prop = TerraformApiConnectionProperty::new(
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
             url::Url::parse("https://app.terraform.io").unwrap(),
             std::env::var("TFVE_TOKEN").unwrap(),
-        );
+        ))
+        .unwrap();
         let organization_name = std::env::var("TFVE_ORGANIZATION_NAME")
             .expect("Failed to read `TFVE_ORGANIZATION_NAME`.");
 
-        let resp = get_workspaces(true, &organization_name, &api_conn_prop)
+        let resp = client
+            .get_workspaces(true, &organization_name)
             .await
             .unwrap();
 
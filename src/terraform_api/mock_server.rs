@@ -0,0 +1,893 @@
+//! In-process fake of HCP Terraform API for tests.
+//!
+//! Serves the projects, workspaces, variable sets and variables endpoints of organizations from
+//! state held in memory, so every flow can be tested without network access. Errors can be injected
+//! to specific requests.
+//!
+//! **API Reference:** https://developer.hashicorp.com/terraform/cloud-docs/api-docs
+
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+use wiremock::{matchers::any, Mock, MockServer, Request, Respond, ResponseTemplate};
+
+use crate::terraform_api::models::VariableCategory;
+
+/// Token accepted by the mock server
+pub const MOCK_TOKEN: &str = "mock-token";
+
+/// Default page size of list endpoints
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+/// Max page size of list endpoints
+const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone)]
+struct MockProject {
+    id: String,
+    organization_name: String,
+    name: String,
+}
+
+#[derive(Debug, Clone)]
+struct MockWorkspace {
+    id: String,
+    organization_name: String,
+    name: String,
+    project_id: Option<String>,
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct MockVariableSet {
+    id: String,
+    organization_name: String,
+    name: String,
+    workspace_ids: Vec<String>,
+    project_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct MockVariable {
+    id: String,
+    /// `workspaces` or `varsets`
+    configurable_type: &'static str,
+    /// ID of the workspace or the variable set
+    configurable_id: String,
+    /// `key`, `value`, `description`, `category`, `hcl` and `sensitive`
+    attributes: serde_json::Map<String, serde_json::Value>,
+    /// `version-id`, changed by every update
+    version_id: String,
+}
+
+/// Error returned instead of the normal response
+#[derive(Debug, Clone)]
+struct InjectedError {
+    method: String,
+    path: String,
+    status: u16,
+    remaining: usize,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    organizations: Vec<String>,
+    projects: Vec<MockProject>,
+    workspaces: Vec<MockWorkspace>,
+    variable_sets: Vec<MockVariableSet>,
+    variables: Vec<MockVariable>,
+    injected_errors: Vec<InjectedError>,
+    max_page_size: Option<usize>,
+    last_id: usize,
+    last_version: usize,
+}
+
+impl MockState {
+    fn new_id(&mut self, prefix: &str) -> String {
+        self.last_id += 1;
+        format!("{}-{:016}", prefix, self.last_id)
+    }
+
+    fn new_version_id(&mut self) -> String {
+        self.last_version += 1;
+        format!("{:064x}", self.last_version)
+    }
+}
+
+/// Fake HCP Terraform server
+#[derive(Debug)]
+pub struct MockTerraformServer {
+    server: MockServer,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTerraformServer {
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        let state = Arc::new(Mutex::new(MockState::default()));
+        Mock::given(any())
+            .respond_with(MockResponder {
+                base_url: url::Url::parse(&server.uri()).unwrap(),
+                state: state.clone(),
+            })
+            .mount(&server)
+            .await;
+
+        Self { server, state }
+    }
+
+    /// Base URL to be passed as `--base-url`
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    pub fn add_organization(&self, organization_name: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.organizations.push(organization_name.to_string());
+    }
+
+    /// Add a project and return its ID.
+    pub fn add_project(&self, organization_name: &str, name: &str) -> String {
+        let mut state = self.state.lock().unwrap();
+        let id = state.new_id("prj");
+        state.projects.push(MockProject {
+            id: id.clone(),
+            organization_name: organization_name.to_string(),
+            name: name.to_string(),
+        });
+        id
+    }
+
+    /// Add a workspace and return its ID.
+    pub fn add_workspace(
+        &self,
+        organization_name: &str,
+        name: &str,
+        project_id: Option<&str>,
+    ) -> String {
+        let mut state = self.state.lock().unwrap();
+        let id = state.new_id("ws");
+        state.workspaces.push(MockWorkspace {
+            id: id.clone(),
+            organization_name: organization_name.to_string(),
+            name: name.to_string(),
+            project_id: project_id.map(|val| val.to_string()),
+            tags: Vec::new(),
+        });
+        id
+    }
+
+    /// Replace the tags of a workspace; key-value tags are given as `key=value`.
+    pub fn set_workspace_tags(&self, workspace_id: &str, tags: &[&str]) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(workspace) = state
+            .workspaces
+            .iter_mut()
+            .find(|val| val.id == workspace_id)
+        {
+            workspace.tags = tags.iter().map(|val| val.to_string()).collect();
+        }
+    }
+
+    /// Add a variable set and return its ID.
+    pub fn add_variable_set(&self, organization_name: &str, name: &str) -> String {
+        let mut state = self.state.lock().unwrap();
+        let id = state.new_id("varset");
+        state.variable_sets.push(MockVariableSet {
+            id: id.clone(),
+            organization_name: organization_name.to_string(),
+            name: name.to_string(),
+            workspace_ids: Vec::new(),
+            project_ids: Vec::new(),
+        });
+        id
+    }
+
+    /// ID of the variable set `name`.
+    pub fn variable_set_id(&self, organization_name: &str, name: &str) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        state
+            .variable_sets
+            .iter()
+            .find(|val| val.organization_name == organization_name && val.name == name)
+            .map(|val| val.id.clone())
+    }
+
+    /// IDs of the workspaces a variable set is applied to.
+    pub fn variable_set_workspaces(&self, varset_id: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state
+            .variable_sets
+            .iter()
+            .find(|val| val.id == varset_id)
+            .map(|val| val.workspace_ids.clone())
+            .unwrap_or_default()
+    }
+
+    /// IDs of the projects a variable set is applied to.
+    pub fn variable_set_projects(&self, varset_id: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state
+            .variable_sets
+            .iter()
+            .find(|val| val.id == varset_id)
+            .map(|val| val.project_ids.clone())
+            .unwrap_or_default()
+    }
+
+    /// Add a variable to a workspace and return its ID.
+    ///
+    /// `attributes` are the `attributes` of a Create Variable request; omitted ones are defaulted.
+    pub fn add_variable(&self, workspace_id: &str, attributes: serde_json::Value) -> String {
+        let mut state = self.state.lock().unwrap();
+        let id = state.new_id("var");
+        let mut variable = MockVariable {
+            id: id.clone(),
+            configurable_type: "workspaces",
+            configurable_id: workspace_id.to_string(),
+            attributes: default_attributes(),
+            version_id: state.new_version_id(),
+        };
+        merge_attributes(&mut variable, &attributes);
+        state.variables.push(variable);
+        id
+    }
+
+    /// Attributes of a variable of a workspace or a variable set including the value of a
+    /// sensitive one.
+    pub fn variable(
+        &self,
+        configurable_id: &str,
+        key: &str,
+        category: VariableCategory,
+    ) -> Option<serde_json::Value> {
+        let state = self.state.lock().unwrap();
+        state
+            .variables
+            .iter()
+            .find(|val| {
+                val.configurable_id == configurable_id
+                    && val.attributes["key"] == key
+                    && val.attributes["category"] == category.as_str()
+            })
+            .map(|val| serde_json::Value::Object(val.attributes.clone()))
+    }
+
+    /// Change the value of a variable like an update by hand, which changes its `version-id`.
+    pub fn set_variable_value(
+        &self,
+        configurable_id: &str,
+        key: &str,
+        category: VariableCategory,
+        value: &str,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let version_id = state.new_version_id();
+        if let Some(variable) = state.variables.iter_mut().find(|val| {
+            val.configurable_id == configurable_id
+                && val.attributes["key"] == key
+                && val.attributes["category"] == category.as_str()
+        }) {
+            variable
+                .attributes
+                .insert(String::from("value"), json!(value));
+            variable.version_id = version_id;
+        }
+    }
+
+    /// Sorted keys of the variables of a workspace or a variable set.
+    pub fn variable_keys(&self, configurable_id: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        let mut keys: Vec<String> = state
+            .variables
+            .iter()
+            .filter(|val| val.configurable_id == configurable_id)
+            .filter_map(|val| val.attributes["key"].as_str().map(|key| key.to_string()))
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Limit the page size of list endpoints to test pagination.
+    pub fn set_max_page_size(&self, max_page_size: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.max_page_size = Some(max_page_size);
+    }
+
+    /// Respond to the next `times` requests of `method` to `path` with `status`.
+    ///
+    /// `429` is sent with `Retry-After: 0` so that retries are not delayed.
+    pub fn inject_error(&self, method: &str, path: &str, status: u16, times: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.injected_errors.push(InjectedError {
+            method: method.to_uppercase(),
+            path: path.to_string(),
+            status,
+            remaining: times,
+        });
+    }
+
+    /// `METHOD path` of the received requests in the order of receipt.
+    pub async fn received_requests(&self) -> Vec<String> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(|val| format!("{} {}", val.method, val.url.path()))
+            .collect()
+    }
+}
+
+/// Route a request to the handler of its endpoint.
+struct MockResponder {
+    /// `request.url` lacks the port of the server, so links are built on this.
+    base_url: url::Url,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Respond for MockResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let mut state = self.state.lock().unwrap();
+        let method = request.method.as_str().to_uppercase();
+        let path = request.url.path().to_string();
+
+        if let Some(injected) = state
+            .injected_errors
+            .iter_mut()
+            .find(|val| val.method == method && val.path == path && 0 < val.remaining)
+        {
+            injected.remaining -= 1;
+            let response = error_response(injected.status, "injected error");
+            return match injected.status {
+                429 => response.insert_header("Retry-After", "0"),
+                _ => response,
+            };
+        }
+
+        let authorized = request
+            .headers
+            .get("authorization")
+            .and_then(|val| val.to_str().ok())
+            == Some(&format!("Bearer {}", MOCK_TOKEN));
+        if !authorized {
+            return error_response(401, "unauthorized");
+        }
+
+        let segments: Vec<&str> = path
+            .trim_start_matches("/api/v2/")
+            .split('/')
+            .filter(|val| !val.is_empty())
+            .collect();
+        match (method.as_str(), segments.as_slice()) {
+            ("GET", ["organizations", organization_name, "projects"]) => {
+                list_projects(&state, &self.base_url, request, organization_name)
+            },
+            ("GET", ["organizations", organization_name, "workspaces"]) => {
+                list_workspaces(&state, &self.base_url, request, organization_name)
+            },
+            ("GET", ["organizations", organization_name, "workspaces", workspace_name]) => {
+                show_workspace(&state, |val| {
+                    val.organization_name == *organization_name && val.name == *workspace_name
+                })
+            },
+            ("GET", ["workspaces", workspace_id]) => {
+                show_workspace(&state, |val| val.id == *workspace_id)
+            },
+            ("GET", ["organizations", organization_name, "varsets"]) => {
+                list_variable_sets(&state, &self.base_url, request, organization_name)
+            },
+            ("POST", ["organizations", organization_name, "varsets"]) => {
+                create_variable_set(&mut state, request, organization_name)
+            },
+            ("GET", ["workspaces", workspace_id, "vars"]) => {
+                list_variables(&state, "workspaces", workspace_id)
+            },
+            ("GET", ["varsets", varset_id, "relationships", "vars"]) => {
+                list_variables(&state, "varsets", varset_id)
+            },
+            ("POST", ["workspaces", workspace_id, "vars"]) => {
+                create_variable(&mut state, request, "workspaces", workspace_id)
+            },
+            ("POST", ["varsets", varset_id, "relationships", "vars"]) => {
+                create_variable(&mut state, request, "varsets", varset_id)
+            },
+            ("POST", ["varsets", varset_id, "relationships", resource_type]) => {
+                apply_variable_set(&mut state, request, varset_id, resource_type)
+            },
+            ("PATCH", ["workspaces", configurable_id, "vars", variable_id])
+            | ("PATCH", ["varsets", configurable_id, "relationships", "vars", variable_id]) => {
+                update_variable(&mut state, request, configurable_id, variable_id)
+            },
+            ("DELETE", ["workspaces", configurable_id, "vars", variable_id])
+            | ("DELETE", ["varsets", configurable_id, "relationships", "vars", variable_id]) => {
+                delete_variable(&mut state, configurable_id, variable_id)
+            },
+            _ => error_response(404, "not found"),
+        }
+    }
+}
+
+fn error_response(status: u16, title: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_json(json!({
+        "errors": [{"status": status.to_string(), "title": title}]
+    }))
+}
+
+fn unprocessable(detail: &str, pointer: &str) -> ResponseTemplate {
+    ResponseTemplate::new(422).set_body_json(json!({
+        "errors": [{
+            "status": "422",
+            "title": "invalid attribute",
+            "detail": detail,
+            "source": {"pointer": pointer}
+        }]
+    }))
+}
+
+fn default_attributes() -> serde_json::Map<String, serde_json::Value> {
+    match json!({
+        "key": "",
+        "value": "",
+        "description": null,
+        "category": "terraform",
+        "hcl": false,
+        "sensitive": false
+    }) {
+        serde_json::Value::Object(val) => val,
+        _ => unreachable!(),
+    }
+}
+
+fn merge_attributes(variable: &mut MockVariable, attributes: &serde_json::Value) {
+    if let Some(attributes) = attributes.as_object() {
+        for (key, val) in attributes {
+            variable.attributes.insert(key.to_string(), val.clone());
+        }
+    }
+}
+
+/// Respond with one page of `data` according to `page[number]` and `page[size]`.
+fn paginated(
+    state: &MockState,
+    base_url: &url::Url,
+    request: &Request,
+    data: Vec<serde_json::Value>,
+) -> ResponseTemplate {
+    let query = |name: &str| {
+        request
+            .url
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .and_then(|(_, val)| val.parse::<usize>().ok())
+    };
+    let page_number = query("page[number]").unwrap_or(1).max(1);
+    let page_size = query("page[size]")
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, state.max_page_size.unwrap_or(MAX_PAGE_SIZE));
+
+    let total_count = data.len();
+    let total_pages = total_count.div_ceil(page_size).max(1);
+    let page: Vec<serde_json::Value> = data
+        .into_iter()
+        .skip((page_number - 1) * page_size)
+        .take(page_size)
+        .collect();
+    let next_page = (page_number < total_pages).then_some(page_number + 1);
+    let next_link = next_page.map(|next_page| {
+        let mut url = base_url.join(request.url.path()).unwrap();
+        url.query_pairs_mut()
+            .extend_pairs(
+                request
+                    .url
+                    .query_pairs()
+                    .filter(|(key, _)| !key.starts_with("page[")),
+            )
+            .append_pair("page[number]", &next_page.to_string())
+            .append_pair("page[size]", &page_size.to_string());
+        url.to_string()
+    });
+
+    ResponseTemplate::new(200).set_body_json(json!({
+        "data": page,
+        "links": {"next": next_link},
+        "meta": {
+            "pagination": {
+                "current-page": page_number,
+                "next-page": next_page,
+                "total-pages": total_pages,
+                "total-count": total_count
+            }
+        }
+    }))
+}
+
+fn list_projects(
+    state: &MockState,
+    base_url: &url::Url,
+    request: &Request,
+    organization_name: &str,
+) -> ResponseTemplate {
+    if !state
+        .organizations
+        .iter()
+        .any(|val| val == organization_name)
+    {
+        return error_response(404, "not found");
+    }
+
+    let data = state
+        .projects
+        .iter()
+        .filter(|val| val.organization_name == organization_name)
+        .map(|val| {
+            json!({
+                "id": val.id,
+                "type": "projects",
+                "attributes": {"name": val.name}
+            })
+        })
+        .collect();
+    paginated(state, base_url, request, data)
+}
+
+fn list_workspaces(
+    state: &MockState,
+    base_url: &url::Url,
+    request: &Request,
+    organization_name: &str,
+) -> ResponseTemplate {
+    if !state
+        .organizations
+        .iter()
+        .any(|val| val == organization_name)
+    {
+        return error_response(404, "not found");
+    }
+
+    // Workspaces having all the tags of `search[tags]` and `filter[tagged][i][key]` and
+    // `filter[tagged][i][value]`, the latter of which are held as `key=value`
+    let query: Vec<(String, String)> = request.url.query_pairs().into_owned().collect();
+    let query_value = |name: &str| {
+        query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, val)| val.to_string())
+    };
+    let mut tags: Vec<String> = query_value("search[tags]")
+        .map(|val| val.split(',').map(|tag| tag.to_string()).collect())
+        .unwrap_or_default();
+    let mut i = 0;
+    while let Some(key) = query_value(&format!("filter[tagged][{}][key]", i)) {
+        let value = query_value(&format!("filter[tagged][{}][value]", i)).unwrap_or_default();
+        tags.push(format!("{}={}", key, value));
+        i += 1;
+    }
+
+    let data = state
+        .workspaces
+        .iter()
+        .filter(|val| val.organization_name == organization_name)
+        .filter(|val| tags.iter().all(|tag| val.tags.contains(tag)))
+        .map(workspace_resource)
+        .collect();
+    paginated(state, base_url, request, data)
+}
+
+fn workspace_resource(workspace: &MockWorkspace) -> serde_json::Value {
+    let project = match &workspace.project_id {
+        Some(project_id) => json!({"data": {"id": project_id, "type": "projects"}}),
+        None => json!({"data": null}),
+    };
+    json!({
+        "id": workspace.id,
+        "type": "workspaces",
+        "attributes": {"name": workspace.name, "tag-names": workspace.tags},
+        "relationships": {"project": project}
+    })
+}
+
+fn show_workspace(
+    state: &MockState,
+    predicate: impl Fn(&MockWorkspace) -> bool,
+) -> ResponseTemplate {
+    match state.workspaces.iter().find(|val| predicate(val)) {
+        Some(workspace) => {
+            ResponseTemplate::new(200).set_body_json(json!({"data": workspace_resource(workspace)}))
+        },
+        None => error_response(404, "not found"),
+    }
+}
+
+/// Resource object of a variable; the value of a sensitive one is hidden like the real API.
+fn variable_resource(variable: &MockVariable) -> serde_json::Value {
+    let mut attributes = variable.attributes.clone();
+    if attributes["sensitive"] == true {
+        attributes.insert(String::from("value"), serde_json::Value::Null);
+    }
+    attributes.insert(String::from("version-id"), json!(variable.version_id));
+    json!({
+        "id": variable.id,
+        "type": "vars",
+        "attributes": attributes,
+        "relationships": {
+            "configurable": {
+                "data": {"id": variable.configurable_id, "type": variable.configurable_type}
+            }
+        }
+    })
+}
+
+/// Validate the attributes of a variable after a Create/Update request and return the error
+/// response if invalid.
+fn validate_variable(state: &MockState, variable: &MockVariable) -> Option<ResponseTemplate> {
+    let key = variable.attributes["key"].as_str().unwrap_or_default();
+    if key.is_empty() {
+        return Some(unprocessable("Key can't be blank", "/data/attributes/key"));
+    }
+    let category = &variable.attributes["category"];
+    if category != "terraform" && category != "env" {
+        return Some(unprocessable(
+            "Category must be one of terraform or env",
+            "/data/attributes/category",
+        ));
+    }
+    let duplicated = state.variables.iter().any(|val| {
+        val.id != variable.id
+            && val.configurable_id == variable.configurable_id
+            && val.attributes["key"] == key
+            && &val.attributes["category"] == category
+    });
+    if duplicated {
+        return Some(unprocessable(
+            "Key has already been taken",
+            "/data/attributes/key",
+        ));
+    }
+
+    None
+}
+
+fn list_variable_sets(
+    state: &MockState,
+    base_url: &url::Url,
+    request: &Request,
+    organization_name: &str,
+) -> ResponseTemplate {
+    if !state
+        .organizations
+        .iter()
+        .any(|val| val == organization_name)
+    {
+        return error_response(404, "not found");
+    }
+
+    let data = state
+        .variable_sets
+        .iter()
+        .filter(|val| val.organization_name == organization_name)
+        .map(variable_set_resource)
+        .collect();
+    paginated(state, base_url, request, data)
+}
+
+fn variable_set_resource(variable_set: &MockVariableSet) -> serde_json::Value {
+    let identifiers = |resource_type: &str, ids: &[String]| -> Vec<serde_json::Value> {
+        ids.iter()
+            .map(|id| json!({"id": id, "type": resource_type}))
+            .collect()
+    };
+    json!({
+        "id": variable_set.id,
+        "type": "varsets",
+        "attributes": {"name": variable_set.name, "global": false},
+        "relationships": {
+            "workspaces": {"data": identifiers("workspaces", &variable_set.workspace_ids)},
+            "projects": {"data": identifiers("projects", &variable_set.project_ids)}
+        }
+    })
+}
+
+fn create_variable_set(
+    state: &mut MockState,
+    request: &Request,
+    organization_name: &str,
+) -> ResponseTemplate {
+    if !state
+        .organizations
+        .iter()
+        .any(|val| val == organization_name)
+    {
+        return error_response(404, "not found");
+    }
+    let body: serde_json::Value = match request.body_json() {
+        Ok(val) => val,
+        Err(_) => return error_response(400, "bad request"),
+    };
+    if body["data"]["type"] != "varsets" {
+        return error_response(409, "conflict");
+    }
+    let name = body["data"]["attributes"]["name"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    if name.is_empty() {
+        return unprocessable("Name can't be blank", "/data/attributes/name");
+    }
+    if state
+        .variable_sets
+        .iter()
+        .any(|val| val.organization_name == organization_name && val.name == name)
+    {
+        return unprocessable("Name has already been taken", "/data/attributes/name");
+    }
+
+    let variable_set = MockVariableSet {
+        id: state.new_id("varset"),
+        organization_name: organization_name.to_string(),
+        name,
+        workspace_ids: Vec::new(),
+        project_ids: Vec::new(),
+    };
+    let response = json!({"data": variable_set_resource(&variable_set)});
+    state.variable_sets.push(variable_set);
+    ResponseTemplate::new(201).set_body_json(response)
+}
+
+/// Apply a variable set to the `workspaces` or `projects` in the request.
+fn apply_variable_set(
+    state: &mut MockState,
+    request: &Request,
+    varset_id: &str,
+    resource_type: &str,
+) -> ResponseTemplate {
+    let body: serde_json::Value = match request.body_json() {
+        Ok(val) => val,
+        Err(_) => return error_response(400, "bad request"),
+    };
+    let Some(identifiers) = body["data"].as_array() else {
+        return error_response(400, "bad request");
+    };
+    let mut ids = Vec::new();
+    for identifier in identifiers {
+        let id = identifier["id"].as_str().unwrap_or_default().to_string();
+        let exists = match resource_type {
+            "workspaces" => state.workspaces.iter().any(|val| val.id == id),
+            "projects" => state.projects.iter().any(|val| val.id == id),
+            _ => false,
+        };
+        if identifier["type"] != resource_type || !exists {
+            return error_response(404, "not found");
+        }
+        ids.push(id);
+    }
+    let Some(variable_set) = state
+        .variable_sets
+        .iter_mut()
+        .find(|val| val.id == varset_id)
+    else {
+        return error_response(404, "not found");
+    };
+
+    let applied = match resource_type {
+        "workspaces" => &mut variable_set.workspace_ids,
+        _ => &mut variable_set.project_ids,
+    };
+    for id in ids {
+        if !applied.contains(&id) {
+            applied.push(id);
+        }
+    }
+    ResponseTemplate::new(204)
+}
+
+fn configurable_exists(state: &MockState, configurable_type: &str, configurable_id: &str) -> bool {
+    match configurable_type {
+        "workspaces" => state.workspaces.iter().any(|val| val.id == configurable_id),
+        _ => state
+            .variable_sets
+            .iter()
+            .any(|val| val.id == configurable_id),
+    }
+}
+
+fn list_variables(
+    state: &MockState,
+    configurable_type: &str,
+    configurable_id: &str,
+) -> ResponseTemplate {
+    if !configurable_exists(state, configurable_type, configurable_id) {
+        return error_response(404, "not found");
+    }
+
+    let data: Vec<serde_json::Value> = state
+        .variables
+        .iter()
+        .filter(|val| val.configurable_id == configurable_id)
+        .map(variable_resource)
+        .collect();
+    ResponseTemplate::new(200).set_body_json(json!({"data": data}))
+}
+
+fn create_variable(
+    state: &mut MockState,
+    request: &Request,
+    configurable_type: &'static str,
+    configurable_id: &str,
+) -> ResponseTemplate {
+    if !configurable_exists(state, configurable_type, configurable_id) {
+        return error_response(404, "not found");
+    }
+    let body: serde_json::Value = match request.body_json() {
+        Ok(val) => val,
+        Err(_) => return error_response(400, "bad request"),
+    };
+    if body["data"]["type"] != "vars" {
+        return error_response(409, "conflict");
+    }
+
+    let mut variable = MockVariable {
+        id: state.new_id("var"),
+        configurable_type,
+        configurable_id: configurable_id.to_string(),
+        attributes: default_attributes(),
+        version_id: state.new_version_id(),
+    };
+    merge_attributes(&mut variable, &body["data"]["attributes"]);
+    if let Some(response) = validate_variable(state, &variable) {
+        return response;
+    }
+
+    let response = json!({"data": variable_resource(&variable)});
+    state.variables.push(variable);
+    ResponseTemplate::new(201).set_body_json(response)
+}
+
+fn update_variable(
+    state: &mut MockState,
+    request: &Request,
+    configurable_id: &str,
+    variable_id: &str,
+) -> ResponseTemplate {
+    let Some(index) = state
+        .variables
+        .iter()
+        .position(|val| val.configurable_id == configurable_id && val.id == variable_id)
+    else {
+        return error_response(404, "not found");
+    };
+    let body: serde_json::Value = match request.body_json() {
+        Ok(val) => val,
+        Err(_) => return error_response(400, "bad request"),
+    };
+    if body["data"]["type"] != "vars" {
+        return error_response(409, "conflict");
+    }
+
+    let mut variable = state.variables[index].clone();
+    merge_attributes(&mut variable, &body["data"]["attributes"]);
+    variable.version_id = state.new_version_id();
+    if let Some(response) = validate_variable(state, &variable) {
+        return response;
+    }
+
+    let response = json!({"data": variable_resource(&variable)});
+    state.variables[index] = variable;
+    ResponseTemplate::new(200).set_body_json(response)
+}
+
+fn delete_variable(
+    state: &mut MockState,
+    configurable_id: &str,
+    variable_id: &str,
+) -> ResponseTemplate {
+    let count = state.variables.len();
+    state
+        .variables
+        .retain(|val| !(val.configurable_id == configurable_id && val.id == variable_id));
+    if state.variables.len() == count {
+        return error_response(404, "not found");
+    }
+
+    ResponseTemplate::new(204)
+}
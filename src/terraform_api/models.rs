@@ -0,0 +1,334 @@
+//! Models of JSON:API documents returned by HCP Terraform API.
+//!
+//! **API Reference:** https://developer.hashicorp.com/terraform/cloud-docs/api-docs#json-api-documents
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// JSON:API document
+#[derive(Debug, Deserialize)]
+pub struct Document<T> {
+    data: T,
+    #[serde(default)]
+    links: Option<Links>,
+    #[serde(default)]
+    meta: Option<Meta>,
+}
+
+impl<T> Document<T> {
+    #[cfg(test)]
+    pub fn get_data(&self) -> &T {
+        &self.data
+    }
+
+    pub fn into_data(self) -> T {
+        self.data
+    }
+
+    /// `links.next`
+    pub fn get_next_link(&self) -> Option<&str> {
+        self.links.as_ref().and_then(|val| val.next.as_deref())
+    }
+
+    /// `meta.pagination.next-page`
+    pub fn get_next_page(&self) -> Option<u64> {
+        self.meta
+            .as_ref()
+            .and_then(|val| val.pagination.as_ref())
+            .and_then(|val| val.next_page)
+    }
+}
+
+/// Links of a JSON:API document
+#[derive(Debug, Deserialize)]
+struct Links {
+    #[serde(default)]
+    next: Option<String>,
+}
+
+/// Meta of a JSON:API document
+#[derive(Debug, Deserialize)]
+struct Meta {
+    #[serde(default)]
+    pagination: Option<Pagination>,
+}
+
+/// Pagination of a JSON:API document
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Pagination {
+    #[serde(default)]
+    next_page: Option<u64>,
+}
+
+/// JSON:API resource object
+#[derive(Debug, Deserialize)]
+pub struct Resource<A> {
+    id: String,
+    attributes: A,
+    #[serde(default)]
+    relationships: HashMap<String, Relationship>,
+}
+
+impl<A> Resource<A> {
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_attributes(&self) -> &A {
+        &self.attributes
+    }
+
+    /// ID of a to-one relationship, or `None` if the relationship is absent or empty.
+    pub fn get_related_id(&self, relationship: &str) -> Option<&str> {
+        match self.relationships.get(relationship)?.data.as_ref()? {
+            RelationshipData::One(val) => Some(&val.id),
+            RelationshipData::Many(_) => None,
+        }
+    }
+
+    /// IDs of a to-many relationship; empty if the relationship is absent.
+    pub fn get_related_ids(&self, relationship: &str) -> Vec<&str> {
+        match self
+            .relationships
+            .get(relationship)
+            .and_then(|val| val.data.as_ref())
+        {
+            Some(RelationshipData::Many(val)) => val.iter().map(|v| v.id.as_str()).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// JSON:API relationship object
+#[derive(Debug, Deserialize)]
+struct Relationship {
+    #[serde(default)]
+    data: Option<RelationshipData>,
+}
+
+/// Resource linkage of a relationship
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RelationshipData {
+    One(ResourceIdentifier),
+    Many(Vec<ResourceIdentifier>),
+}
+
+/// JSON:API resource identifier object
+#[derive(Debug, Deserialize)]
+struct ResourceIdentifier {
+    id: String,
+}
+
+/// Attributes of `workspaces`
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceAttributes {
+    name: String,
+}
+
+impl WorkspaceAttributes {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Attributes of `projects`
+#[derive(Debug, Deserialize)]
+pub struct ProjectAttributes {
+    name: String,
+}
+
+impl ProjectAttributes {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Attributes of `varsets`
+#[derive(Debug, Deserialize)]
+pub struct VarsetAttributes {
+    name: String,
+}
+
+impl VarsetAttributes {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Category of `vars`
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableCategory {
+    /// Terraform variable
+    #[default]
+    Terraform,
+    /// Environment variable
+    Env,
+}
+
+impl VariableCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VariableCategory::Terraform => "terraform",
+            VariableCategory::Env => "env",
+        }
+    }
+}
+
+impl std::fmt::Display for VariableCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for VariableCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "terraform" => Ok(VariableCategory::Terraform),
+            "env" => Ok(VariableCategory::Env),
+            _ => Err(format!(
+                "Unknown category `{}`, expected `terraform` or `env`.",
+                s
+            )),
+        }
+    }
+}
+
+/// Attributes of `vars`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct VarAttributes {
+    key: String,
+    /// `None` if the variable is sensitive.
+    #[serde(default)]
+    value: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    category: VariableCategory,
+    #[serde(default)]
+    hcl: bool,
+    #[serde(default)]
+    sensitive: bool,
+    /// Changed by every update, including the value of a sensitive variable which is not returned.
+    #[serde(
+        rename = "version-id",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    version_id: Option<String>,
+}
+
+impl VarAttributes {
+    pub fn get_key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn get_value(&self) -> &Option<String> {
+        &self.value
+    }
+
+    pub fn get_description(&self) -> &Option<String> {
+        &self.description
+    }
+
+    pub fn get_category(&self) -> VariableCategory {
+        self.category
+    }
+
+    pub fn is_hcl(&self) -> bool {
+        self.hcl
+    }
+
+    pub fn is_sensitive(&self) -> bool {
+        self.sensitive
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_workspace_document() {
+        let doc: Document<Vec<Resource<WorkspaceAttributes>>> = serde_json::from_value(json!({
+            "data": [
+                {
+                    "id": "ws-1",
+                    "type": "workspaces",
+                    "attributes": {"name": "workspace-1", "auto-apply": false},
+                    "relationships": {
+                        "project": {"data": {"id": "prj-1", "type": "projects"}},
+                        "remote-state-consumers": {"links": {"related": "/api/v2/workspaces/ws-1/relationships/remote-state-consumers"}}
+                    }
+                },
+                {
+                    "id": "ws-2",
+                    "type": "workspaces",
+                    "attributes": {"name": "workspace-2"}
+                }
+            ],
+            "links": {"next": null},
+            "meta": {"pagination": {"current-page": 1, "next-page": 2}}
+        }))
+        .unwrap();
+
+        assert_eq!(doc.get_next_link(), None);
+        assert_eq!(doc.get_next_page(), Some(2));
+        let workspaces = doc.into_data();
+        assert_eq!(workspaces.first().unwrap().get_id(), "ws-1");
+        assert_eq!(
+            workspaces.first().unwrap().get_attributes().get_name(),
+            "workspace-1"
+        );
+        assert_eq!(
+            workspaces.first().unwrap().get_related_id("project"),
+            Some("prj-1")
+        );
+        assert_eq!(
+            workspaces
+                .first()
+                .unwrap()
+                .get_related_id("remote-state-consumers"),
+            None
+        );
+        // No project relationship
+        assert_eq!(workspaces.get(1).unwrap().get_related_id("project"), None);
+    }
+
+    #[test]
+    fn test_var_document() {
+        let doc: Document<Resource<VarAttributes>> = serde_json::from_value(json!({
+            "data": {
+                "id": "var-1",
+                "type": "vars",
+                "attributes": {
+                    "key": "secret",
+                    "value": null,
+                    "description": null,
+                    "category": "env",
+                    "hcl": false,
+                    "sensitive": true
+                }
+            }
+        }))
+        .unwrap();
+
+        let attributes = doc.get_data().get_attributes();
+        assert_eq!(attributes.get_key(), "secret");
+        assert_eq!(attributes.get_value(), &None);
+        assert_eq!(attributes.get_category(), VariableCategory::Env);
+        assert!(attributes.is_sensitive());
+
+        // Malformed
+        let doc = serde_json::from_value::<Document<Resource<VarAttributes>>>(
+            json!({"data": {"id": "var-1", "type": "vars", "attributes": {"value": "a"}}}),
+        );
+        assert!(doc.is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! Follow the pagination of JSON:API list endpoints.
+//!
+//! **API Reference:** https://developer.hashicorp.com/terraform/cloud-docs/api-docs#pagination
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    error::TfvarExportError,
+    terraform_api::{
+        client::TerraformClient,
+        models::{Document, Resource},
+    },
+};
+
+/// Max element numbers per page.
+///
+/// ## Reference
+///
+/// https://developer.hashicorp.com/terraform/cloud-docs/api-docs/projects#list-projects
+pub const TERRAFORM_API_QS_PAGE_SIZE: u8 = 100;
+
+impl TerraformClient {
+    /// Get every page of a list endpoint and return the resources of all pages.
+    ///
+    /// The next page is read from `links.next` and, if absent, from `meta.pagination.next-page`.
+    pub async fn get_all_pages<A: DeserializeOwned>(
+        &self,
+        path: &str,
+    ) -> Result<Vec<Resource<A>>, TfvarExportError> {
+        self.get_all_pages_with_query(path, &[]).await
+    }
+
+    /// Get every page of a list endpoint with query parameters such as filters.
+    pub async fn get_all_pages_with_query<A: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<Resource<A>>, TfvarExportError> {
+        let mut url = self.url(path);
+        url.query_pairs_mut()
+            .extend_pairs(query)
+            .append_pair("page[number]", "1")
+            .append_pair("page[size]", &TERRAFORM_API_QS_PAGE_SIZE.to_string());
+
+        let mut result = Vec::new();
+        let mut next_url = Some(url);
+        while let Some(url) = next_url.take() {
+            let document: Document<Vec<Resource<A>>> = self
+                .send_json(
+                    reqwest::Method::GET,
+                    url.clone(),
+                    None,
+                    reqwest::StatusCode::OK,
+                )
+                .await?;
+
+            next_url = next_page_url(&url, &document)?;
+            result.extend(document.into_data());
+        }
+
+        Ok(result)
+    }
+}
+
+/// Return the URL of the next page, or `None` if the current page is the last one.
+fn next_page_url<T>(
+    current_url: &url::Url,
+    document: &Document<T>,
+) -> Result<Option<url::Url>, TfvarExportError> {
+    if let Some(next) = document.get_next_link() {
+        let next_url = current_url.join(next).map_err(|err| {
+            TfvarExportError::Parse(format!("Invalid `links.next` `{}`: {}", next, err))
+        })?;
+        // Never send the token to another host.
+        if next_url.origin() != current_url.origin() {
+            return Err(TfvarExportError::Parse(format!(
+                "Refusing to follow `links.next` to `{}`.",
+                next_url
+            )));
+        }
+        return Ok(Some(next_url));
+    }
+
+    match document.get_next_page() {
+        Some(next_page) => {
+            let pairs: Vec<(String, String)> = current_url
+                .query_pairs()
+                .filter(|(key, _)| key != "page[number]")
+                .map(|(key, val)| (key.into_owned(), val.into_owned()))
+                .collect();
+            let mut next_url = current_url.clone();
+            next_url
+                .query_pairs_mut()
+                .clear()
+                .extend_pairs(pairs)
+                .append_pair("page[number]", &next_page.to_string());
+            Ok(Some(next_url))
+        },
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock,
+        MockServer,
+        ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::terraform_api::connection_prop::TerraformApiConnectionProperty;
+
+    /// Build a document of an empty page with `links` and `meta` in `val`.
+    fn document(mut val: serde_json::Value) -> Document<Vec<serde_json::Value>> {
+        val["data"] = json!([]);
+        serde_json::from_value(val).unwrap()
+    }
+
+    #[test]
+    fn test_next_page_url() {
+        let current_url = url::Url::parse(
+            "https://app.terraform.io/api/v2/organizations/org/workspaces?page%5Bnumber%5D=1&page%5Bsize%5D=100",
+        )
+        .unwrap();
+
+        // `links.next` takes precedence
+        let next = next_page_url(
+            &current_url,
+            &document(json!({
+                "links": {"next": "https://app.terraform.io/api/v2/organizations/org/workspaces?page%5Bnumber%5D=3"},
+                "meta": {"pagination": {"next-page": 2}}
+            })),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            next.query_pairs()
+                .find(|(k, _)| k == "page[number]")
+                .unwrap()
+                .1,
+            "3"
+        );
+
+        // `meta.pagination.next-page` keeps the other query parameters
+        let next = next_page_url(
+            &current_url,
+            &document(json!({"links": {"next": null}, "meta": {"pagination": {"next-page": 2}}})),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            next.query_pairs()
+                .find(|(k, _)| k == "page[number]")
+                .unwrap()
+                .1,
+            "2"
+        );
+        assert_eq!(
+            next.query_pairs()
+                .find(|(k, _)| k == "page[size]")
+                .unwrap()
+                .1,
+            "100"
+        );
+
+        // Last page
+        let next = next_page_url(
+            &current_url,
+            &document(
+                json!({"links": {"next": null}, "meta": {"pagination": {"next-page": null}}}),
+            ),
+        )
+        .unwrap();
+        assert!(next.is_none());
+
+        // Another host
+        let next = next_page_url(
+            &current_url,
+            &document(
+                json!({"links": {"next": "https://example.com/api/v2/organizations/org/workspaces"}}),
+            ),
+        );
+        assert!(next.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_all_pages() {
+        let server = MockServer::start().await;
+        for page in 1..=3 {
+            let next_page = if page < 3 {
+                json!(page + 1)
+            } else {
+                json!(null)
+            };
+            Mock::given(method("GET"))
+                .and(path("/api/v2/things"))
+                .and(query_param("page[number]", page.to_string()))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "data": [
+                        {"id": format!("id-{}-a", page), "type": "things", "attributes": {}},
+                        {"id": format!("id-{}-b", page), "type": "things", "attributes": {}}
+                    ],
+                    "meta": {"pagination": {"current-page": page, "next-page": next_page}}
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap();
+        let resp = client
+            .get_all_pages::<serde_json::Value>("/api/v2/things")
+            .await
+            .unwrap();
+
+        let ids: Vec<&str> = resp.iter().map(|val| val.get_id()).collect();
+        assert_eq!(ids, vec![
+            "id-1-a", "id-1-b", "id-2-a", "id-2-b", "id-3-a", "id-3-b"
+        ]);
+    }
+}
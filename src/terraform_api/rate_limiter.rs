@@ -0,0 +1,98 @@
+//! Rate limiter shared by every API call.
+//!
+//! **API Reference:** https://developer.hashicorp.com/terraform/cloud-docs/api-docs#rate-limiting
+
+use std::sync::Arc;
+
+use crate::error::TfvarExportError;
+
+/// Default max requests per second
+///
+/// HCP Terraform allows 30 requests per second per user, so keep a margin.
+pub const DEFAULT_REQUESTS_PER_SECOND: u64 = 20;
+
+/// Async token bucket
+///
+/// Clones share the same bucket, so every request of a `TerraformClient` and its clones is limited
+/// together regardless of the workspace.
+#[derive(Clone)]
+pub struct RateLimiter {
+    ratelimiter: Arc<ratelimit::Ratelimiter>,
+}
+
+impl std::fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("requests_per_second", &self.ratelimiter.rate())
+            .finish()
+    }
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: u64) -> Result<Self, TfvarExportError> {
+        if requests_per_second == 0 {
+            return Err(TfvarExportError::Config(String::from(
+                "Rate limit must be at least 1 request per second.",
+            )));
+        }
+
+        let ratelimiter =
+            ratelimit::Ratelimiter::builder(requests_per_second, std::time::Duration::from_secs(1))
+                .max_tokens(requests_per_second)
+                .initial_available(requests_per_second)
+                .build()
+                .map_err(|err| {
+                    TfvarExportError::Config(format!(
+                        "Invalid rate limit `{}`: {}",
+                        requests_per_second, err
+                    ))
+                })?;
+
+        Ok(Self {
+            ratelimiter: Arc::new(ratelimiter),
+        })
+    }
+
+    /// Wait until a token is available and take it.
+    ///
+    /// Never fails, so no request is dropped.
+    pub async fn wait(&self) {
+        while let Err(sleep) = self.ratelimiter.try_wait() {
+            tokio::time::sleep(sleep).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wait() {
+        const REQUESTS_PER_SECOND: u64 = 5;
+
+        let rate_limiter = RateLimiter::new(REQUESTS_PER_SECOND).unwrap();
+        let start = std::time::Instant::now();
+
+        // Clones share the bucket; the burst is consumed and then the next second is waited.
+        let tasks: Vec<_> = (0..REQUESTS_PER_SECOND * 2)
+            .map(|_| {
+                let rate_limiter = rate_limiter.clone();
+                tokio::spawn(async move { rate_limiter.wait().await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert!(std::time::Duration::from_millis(900) <= start.elapsed());
+    }
+
+    #[test]
+    fn test_new_invalid() {
+        assert!(matches!(
+            RateLimiter::new(0),
+            Err(TfvarExportError::Config(_))
+        ));
+    }
+}
@@ -1,18 +1,142 @@
-//! Update or create Terraform Cloud workspace variable.
+//! Update or create Terraform Cloud workspace or variable set variable.
 //!
-//! **API Reference:** https://developer.hashicorp.com/terraform/cloud-docs/api-docs/workspace-variables
+//! **API Reference:**
+//! - https://developer.hashicorp.com/terraform/cloud-docs/api-docs/workspace-variables
+//! - https://developer.hashicorp.com/terraform/cloud-docs/api-docs/variable-sets
 
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::terraform_api::connection_prop::TerraformApiConnectionProperty;
+use crate::{
+    error::TfvarExportError,
+    terraform_api::{
+        client::TerraformClient,
+        models::{Document, Resource, VarAttributes, VariableCategory},
+    },
+};
+
+/// Owner of variables
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "id", rename_all = "snake_case")]
+pub enum VariableTarget {
+    /// Workspace of the ID
+    Workspace(String),
+    /// Variable set of the ID
+    VariableSet(String),
+}
+
+impl VariableTarget {
+    /// Path of the variables of the target
+    pub fn vars_path(&self) -> String {
+        match self {
+            VariableTarget::Workspace(id) => format!("/api/v2/workspaces/{}/vars", id),
+            VariableTarget::VariableSet(id) => {
+                format!("/api/v2/varsets/{}/relationships/vars", id)
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for VariableTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariableTarget::Workspace(id) => write!(f, "workspace ID: {}", id),
+            VariableTarget::VariableSet(id) => write!(f, "variable set ID: {}", id),
+        }
+    }
+}
+
+/// Encoding of a value into a variable
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValueEncoding {
+    /// Strings as is, lists, maps and objects as HCL, and the other types as their literals.
+    #[default]
+    Auto,
+    /// Every value as HCL.
+    Hcl,
+    /// Strings as is and the other types as JSON, not as HCL.
+    String,
+    /// Every value as JSON, not as HCL, to be read with `jsondecode`.
+    JsonString,
+    /// Strings as is as HCL, for values already written in HCL such as restored ones.
+    ///
+    /// Not available in the export list.
+    RawHcl,
+}
+
+impl ValueEncoding {
+    /// Encode `value` and return the variable value and whether it is HCL.
+    pub fn encode(&self, value: &serde_json::Value) -> (String, bool) {
+        let data_value = match value.as_str() {
+            Some(val) if self.is_raw_string(value) => val.to_string(),
+            _ => value.to_string(),
+        };
+        let is_hcl = match self {
+            ValueEncoding::Auto => value.is_array() || value.is_object(),
+            ValueEncoding::Hcl | ValueEncoding::RawHcl => true,
+            ValueEncoding::String | ValueEncoding::JsonString => false,
+        };
+
+        (data_value, is_hcl)
+    }
+
+    /// Whether `value` is registered as is rather than as its JSON representation.
+    pub fn is_raw_string(&self, value: &serde_json::Value) -> bool {
+        value.is_string()
+            && matches!(
+                self,
+                ValueEncoding::Auto | ValueEncoding::String | ValueEncoding::RawHcl
+            )
+    }
+}
+
+impl std::str::FromStr for ValueEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ValueEncoding::Auto),
+            "hcl" => Ok(ValueEncoding::Hcl),
+            "string" => Ok(ValueEncoding::String),
+            "json-string" => Ok(ValueEncoding::JsonString),
+            _ => Err(format!(
+                "Unknown encoding `{}`, expected `auto`, `hcl`, `string` or `json-string`.",
+                s
+            )),
+        }
+    }
+}
 
 /// Terraform variable property
-#[derive(Debug)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TerraformVariableProperty {
     variable_id: Option<String>,
     variable_name: String,
     variable_description: Option<String>,
     value: serde_json::Value,
+    category: VariableCategory,
+    sensitive: bool,
+    encoding: ValueEncoding,
+}
+
+/// The value of a sensitive variable is never printed.
+impl std::fmt::Debug for TerraformVariableProperty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value: &dyn std::fmt::Debug = match self.sensitive {
+            true => &"(sensitive value)",
+            false => &self.value,
+        };
+        f.debug_struct("TerraformVariableProperty")
+            .field("variable_id", &self.variable_id)
+            .field("variable_name", &self.variable_name)
+            .field("variable_description", &self.variable_description)
+            .field("value", value)
+            .field("category", &self.category)
+            .field("sensitive", &self.sensitive)
+            .field("encoding", &self.encoding)
+            .finish()
+    }
 }
 
 impl TerraformVariableProperty {
@@ -27,9 +151,27 @@ impl TerraformVariableProperty {
             variable_name,
             variable_description,
             value,
+            category: VariableCategory::default(),
+            sensitive: false,
+            encoding: ValueEncoding::default(),
         }
     }
 
+    pub fn with_category(mut self, category: VariableCategory) -> Self {
+        self.category = category;
+        self
+    }
+
+    pub fn with_sensitive(mut self, sensitive: bool) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: ValueEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
     fn get_variable_id(&self) -> &Option<String> {
         &self.variable_id
     }
@@ -38,13 +180,77 @@ impl TerraformVariableProperty {
         &self.variable_name
     }
 
-    fn get_variable_description(&self) -> &Option<String> {
+    pub fn get_variable_description(&self) -> &Option<String> {
         &self.variable_description
     }
 
     fn get_value(&self) -> &serde_json::Value {
         &self.value
     }
+
+    fn get_category(&self) -> VariableCategory {
+        self.category
+    }
+
+    pub fn is_sensitive(&self) -> bool {
+        self.sensitive
+    }
+
+    fn get_encoding(&self) -> ValueEncoding {
+        self.encoding
+    }
+
+    /// Encode the value and return the variable value and whether it is HCL.
+    pub fn encode(&self) -> (String, bool) {
+        self.encoding.encode(&self.value)
+    }
+
+    /// Whether the existing variable already has the value, description, HCL flag and
+    /// sensitivity of this property, so that updating it changes nothing.
+    ///
+    /// Values other than raw strings are compared semantically, i.e. HCL and JSON values are the
+    /// same if they are parsed into the same value regardless of whitespace or order of keys.
+    /// A sensitive variable is never up to date as its value cannot be read.
+    pub fn is_up_to_date(&self, existing: &VarAttributes) -> bool {
+        if self.sensitive || existing.is_sensitive() {
+            return false;
+        }
+
+        let (data_value, is_hcl) = self.encoding.encode(&self.value);
+        let description = self.variable_description.as_deref().unwrap_or_default();
+        if is_hcl != existing.is_hcl()
+            || description != existing.get_description().as_deref().unwrap_or_default()
+        {
+            return false;
+        }
+
+        let existing_value = match existing.get_value() {
+            Some(val) => val,
+            None => return false,
+        };
+        if self.encoding.is_raw_string(&self.value) {
+            return &data_value == existing_value;
+        }
+        match (
+            parse_value(&data_value, is_hcl),
+            parse_value(existing_value, is_hcl),
+        ) {
+            (Some(desired), Some(existing)) => desired == existing,
+            _ => &data_value == existing_value,
+        }
+    }
+}
+
+/// Parse a variable value as HCL or JSON; `None` if it is not valid.
+pub fn parse_value(value: &str, is_hcl: bool) -> Option<serde_json::Value> {
+    match is_hcl {
+        true => {
+            let mut body: serde_json::Value =
+                hcl::from_str(&format!("value = {}\n", value)).ok()?;
+            Some(body.get_mut("value")?.take())
+        },
+        false => serde_json::from_str(value).ok(),
+    }
 }
 
 /// Terraform variable Create/Update result
@@ -79,334 +285,758 @@ impl TerraformVariableRegistrationResult {
     }
 }
 
-/// Update Terraform Workspace variable(s).
+/// Build a `TerraformVariableRegistrationResult` from a Create/Update response.
 ///
-/// ## Remark
-///
-/// To prevent [`Rate Limiting`](https://developer.hashicorp.com/terraform/cloud-docs/api-docs#rate-limiting),
-/// limit the rate 20 requests per second.
-pub async fn update_variable(
-    workspace_id: &str,
-    api_conn_prop: &TerraformApiConnectionProperty,
-    terraform_variable_property: &Vec<TerraformVariableProperty>,
-) -> Result<Vec<TerraformVariableRegistrationResult>, Box<dyn std::error::Error>> {
-    let mut url = api_conn_prop.base_url().clone();
-    let token = api_conn_prop.token();
-
-    log::info!("Processing workspace ID: {}.", workspace_id);
-
-    let mut result = Vec::new();
-
-    // Limit the rate 20 requests per second.
-    let ratelimiter = ratelimit::Ratelimiter::builder(20, std::time::Duration::from_secs(1))
-        .max_tokens(20)
-        .initial_available(20)
-        .build()
-        .unwrap();
-    let count = terraform_variable_property.len();
-    for i in 0..count {
-        let path = format!(
-            "/api/v2/workspaces/{}/vars/{}",
-            workspace_id,
-            terraform_variable_property
+/// Values other than raw strings are registered as their JSON representation, so decode them back.
+/// The value of a sensitive variable is never returned by the API and left `null`.
+fn registration_result(
+    document: Document<Resource<VarAttributes>>,
+    is_string: bool,
+) -> Result<TerraformVariableRegistrationResult, TfvarExportError> {
+    let resource = document.into_data();
+    let attributes = resource.get_attributes();
+    let value = match attributes.get_value() {
+        _ if attributes.is_sensitive() => serde_json::Value::Null,
+        Some(raw_value) if is_string => serde_json::Value::String(raw_value.to_owned()),
+        Some(raw_value) => serde_json::from_str::<serde_json::Value>(raw_value)?,
+        None => serde_json::Value::Null,
+    };
+
+    Ok(TerraformVariableRegistrationResult {
+        variable_id: resource.get_id().to_string(),
+        variable_name: attributes.get_key().to_string(),
+        variable_description: attributes.get_description().clone().unwrap_or_default(),
+        value,
+    })
+}
+
+impl TerraformClient {
+    /// Update Terraform Workspace or variable set variable(s).
+    ///
+    /// ## Remark
+    ///
+    /// To prevent [`Rate Limiting`](https://developer.hashicorp.com/terraform/cloud-docs/api-docs#rate-limiting),
+    /// every request waits for the rate limiter shared by the client.
+    pub async fn update_variable(
+        &self,
+        target: &VariableTarget,
+        terraform_variable_property: &[TerraformVariableProperty],
+    ) -> Result<Vec<TerraformVariableRegistrationResult>, TfvarExportError> {
+        log::info!("Processing {}.", target);
+
+        let mut result = Vec::new();
+
+        let count = terraform_variable_property.len();
+        for i in 0..count {
+            let variable_id = terraform_variable_property
                 .get(i)
                 .unwrap()
                 .get_variable_id()
                 .clone()
+                .ok_or_else(|| {
+                    TfvarExportError::Validation(format!(
+                        "Variable ID of `{}` is required for update.",
+                        terraform_variable_property
+                            .get(i)
+                            .unwrap()
+                            .get_variable_name()
+                    ))
+                })?;
+            let path = format!("{}/{}", target.vars_path(), variable_id);
+
+            let encoding = terraform_variable_property.get(i).unwrap().get_encoding();
+            let value = terraform_variable_property.get(i).unwrap().get_value();
+            let (data_value, is_hcl) = encoding.encode(value);
+            let is_string = encoding.is_raw_string(value);
+
+            let description = match &terraform_variable_property
+                .get(i)
                 .unwrap()
-        );
-        url.set_path(&path);
-
-        if let Err(sleep) = ratelimiter.try_wait() {
-            std::thread::sleep(sleep);
-            continue;
+                .get_variable_description()
+            {
+                Some(val) => val,
+                None => "",
+            };
+
+            let data = json!({
+                "data":{
+                    "id": variable_id,
+                    "type": "vars",
+                    "attributes": {
+                        "key": terraform_variable_property.get(i).unwrap().get_variable_name(),
+                        "value": data_value,
+                        "description": description,
+                        "category": terraform_variable_property.get(i).unwrap().get_category().as_str(),
+                        "hcl": is_hcl,
+                        "sensitive": terraform_variable_property.get(i).unwrap().is_sensitive()
+                      }
+                  }
+            });
+
+            let json_value: Document<Resource<VarAttributes>> = self
+                .send_json(
+                    reqwest::Method::PATCH,
+                    self.url(&path),
+                    Some(&data),
+                    reqwest::StatusCode::OK,
+                )
+                .await?;
+            result.push(registration_result(json_value, is_string)?);
         }
 
-        let value_for_type = terraform_variable_property.get(i).unwrap().get_value();
-        let is_hcl = if value_for_type.is_array() || value_for_type.is_object() {
-            true
-        } else {
-            false
-        };
+        log::info!("{} Variable(s) successfully updated.", count);
 
-        let is_string = if terraform_variable_property
-            .get(i)
-            .unwrap()
-            .get_value()
-            .is_string()
-        {
-            true
-        } else {
-            false
-        };
+        Ok(result)
+    }
 
-        let description = match &terraform_variable_property
-            .get(i)
-            .unwrap()
-            .get_variable_description()
-        {
-            Some(val) => val,
-            None => "",
-        };
+    /// Create Terraform Workspace or variable set variable(s).
+    ///
+    /// ## Remark
+    ///
+    /// To prevent [`Rate Limiting`](https://developer.hashicorp.com/terraform/cloud-docs/api-docs#rate-limiting),
+    /// every request waits for the rate limiter shared by the client.
+    pub async fn create_variable(
+        &self,
+        target: &VariableTarget,
+        terraform_variable_property: &[TerraformVariableProperty],
+    ) -> Result<Vec<TerraformVariableRegistrationResult>, TfvarExportError> {
+        let path = target.vars_path();
 
-        let data_value = if is_string {
-            terraform_variable_property
-                .get(i)
-                .unwrap()
-                .get_value()
-                .as_str()
-                .unwrap()
-                .to_string()
-        } else {
-            terraform_variable_property
+        log::info!("Processing {}.", target);
+
+        let mut result = Vec::new();
+
+        let count = terraform_variable_property.len();
+        for i in 0..count {
+            let encoding = terraform_variable_property.get(i).unwrap().get_encoding();
+            let value = terraform_variable_property.get(i).unwrap().get_value();
+            let (data_value, is_hcl) = encoding.encode(value);
+            let is_string = encoding.is_raw_string(value);
+
+            let description = match &terraform_variable_property
                 .get(i)
                 .unwrap()
-                .get_value()
-                .to_string()
-        };
-
-        let data = json!({
-            "data":{
-                "id": terraform_variable_property.get(i).unwrap().get_variable_id().clone().unwrap(),
-                "type": "vars",
-                "attributes": {
-                    "key": terraform_variable_property.get(i).unwrap().get_variable_name(),
-                    "value": data_value,
-                    "description": description,
-                    "category": "terraform",
-                    "hcl": is_hcl
+                .get_variable_description()
+            {
+                Some(val) => val,
+                None => "",
+            };
+
+            let data = json!({
+                "data":{
+                    "type": "vars",
+                    "attributes": {
+                        "key": terraform_variable_property.get(i).unwrap().get_variable_name(),
+                        "value": data_value,
+                        "description": description,
+                        "category": terraform_variable_property.get(i).unwrap().get_category().as_str(),
+                        "hcl": is_hcl,
+                        "sensitive": terraform_variable_property.get(i).unwrap().is_sensitive()
+                      }
                   }
-              }
-        });
-
-        let response = reqwest::Client::new()
-            .patch(url.as_str())
-            .header("Authorization", format!("Bearer {}", token))
-            .header("Content-Type", "application/vnd.api+json")
-            .body(data.to_string())
-            .send()
-            .await?;
+            });
+
+            let json_value: Document<Resource<VarAttributes>> = self
+                .send_json(
+                    reqwest::Method::POST,
+                    self.url(&path),
+                    Some(&data),
+                    reqwest::StatusCode::CREATED,
+                )
+                .await?;
+            result.push(registration_result(json_value, is_string)?);
+        }
 
-        assert!(
-            response.status() == 200,
-            "Response status is {}.",
-            response.status()
-        );
+        log::info!("{} Variable(s) successfully created.", count);
 
-        let json_value: serde_json::Value = serde_json::from_str(&response.text().await.unwrap())?;
-        let value = if is_string {
-            json_value["data"]["attributes"]["value"].clone()
-        } else {
-            serde_json::from_str::<serde_json::Value>(
-                json_value["data"]["attributes"]["value"].as_str().unwrap(),
-            )
-            .unwrap()
-        };
-        result.push(TerraformVariableRegistrationResult {
-            variable_id: json_value["data"]["id"].as_str().unwrap().to_string(),
-            variable_name: json_value["data"]["attributes"]["key"]
-                .as_str()
-                .unwrap()
-                .to_string(),
-            variable_description: json_value["data"]["attributes"]["description"]
-                .as_str()
-                .unwrap()
-                .to_string(),
-            value,
-        });
+        Ok(result)
     }
 
-    log::info!("{} Variable(s) successfully updated.", count);
+    /// Delete Terraform Workspace or variable set variable(s).
+    ///
+    /// ## Remark
+    ///
+    /// To prevent [`Rate Limiting`](https://developer.hashicorp.com/terraform/cloud-docs/api-docs#rate-limiting),
+    /// every request waits for the rate limiter shared by the client.
+    pub async fn delete_variable(
+        &self,
+        target: &VariableTarget,
+        variable_ids: &[String],
+    ) -> Result<(), TfvarExportError> {
+        log::info!("Processing {}.", target);
+
+        for variable_id in variable_ids.iter() {
+            let path = format!("{}/{}", target.vars_path(), variable_id);
+            self.send(
+                reqwest::Method::DELETE,
+                self.url(&path),
+                None,
+                reqwest::StatusCode::NO_CONTENT,
+            )
+            .await?;
+        }
+
+        log::info!("{} Variable(s) successfully deleted.", variable_ids.len());
 
-    Ok(result)
+        Ok(())
+    }
 }
 
-/// Create Terraform Workspace variable(s).
-///
-/// ## Remark
-///
-/// To prevent [`Rate Limiting`](https://developer.hashicorp.com/terraform/cloud-docs/api-docs#rate-limiting),
-/// limit the rate 20 requests per second.
-pub async fn create_variable(
-    workspace_id: &str,
-    api_conn_prop: &TerraformApiConnectionProperty,
-    terraform_variable_property: &Vec<TerraformVariableProperty>,
-) -> Result<Vec<TerraformVariableRegistrationResult>, Box<dyn std::error::Error>> {
-    let mut url = api_conn_prop.base_url().clone();
-    let token = api_conn_prop.token();
-
-    let path = format!("/api/v2/workspaces/{}/vars", workspace_id);
-    url.set_path(&path);
-
-    log::info!("Processing workspace ID: {}.", workspace_id);
-
-    let mut result = Vec::new();
-
-    // Limit the rate 20 requests per second.
-    let ratelimiter = ratelimit::Ratelimiter::builder(20, std::time::Duration::from_secs(1))
-        .max_tokens(20)
-        .initial_available(20)
-        .build()
-        .unwrap();
-    let count = terraform_variable_property.len();
-    for i in 0..count {
-        if let Err(sleep) = ratelimiter.try_wait() {
-            std::thread::sleep(sleep);
-            continue;
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+
+    use wiremock::{
+        matchers::{method, path},
+        Mock,
+        MockServer,
+        Request,
+        Respond,
+        ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{
+        terraform_api::{
+            cassette::Cassette,
+            check_variable_status::variable_status,
+            connection_prop::TerraformApiConnectionProperty,
+            mock_server::{MockTerraformServer, MOCK_TOKEN},
+            rate_limiter::RateLimiter,
+        },
+        utils::get_outputs::get_outputs,
+    };
+
+    /// Respond to a Create variable request with the requested attributes.
+    struct EchoVariable;
+
+    impl Respond for EchoVariable {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let mut body: serde_json::Value = request.body_json().unwrap();
+            body["data"]["id"] = json!(format!("var-{}", body["data"]["attributes"]["key"]));
+            ResponseTemplate::new(201).set_body_json(body)
         }
+    }
 
-        let value_for_type = terraform_variable_property.get(i).unwrap().get_value();
-        let is_hcl = if value_for_type.is_array() || value_for_type.is_object() {
-            true
-        } else {
-            false
-        };
+    #[tokio::test]
+    async fn test_create_variable_api_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v2/workspaces/ws-1/vars"))
+            .respond_with(ResponseTemplate::new(422).set_body_json(json!({
+                "errors": [{
+                    "status": "422",
+                    "title": "invalid attribute",
+                    "detail": "Key has already been taken"
+                }]
+            })))
+            .mount(&server)
+            .await;
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap();
+        let res = client
+            .create_variable(&VariableTarget::Workspace(String::from("ws-1")), &[
+                TerraformVariableProperty::new(None, String::from("key"), None, json!("value")),
+            ])
+            .await;
 
-        let is_string = match &terraform_variable_property.get(i).unwrap().get_value() {
-            x if x.is_string() => true,
-            _ => false,
-        };
+        match res {
+            Err(TfvarExportError::Api { status, errors, .. }) => {
+                assert_eq!(status, 422);
+                assert_eq!(
+                    errors.first().unwrap().to_string(),
+                    "invalid attribute: Key has already been taken"
+                );
+            },
+            _ => panic!("Expected an API error."),
+        }
+    }
 
-        let description = match &terraform_variable_property
-            .get(i)
+    #[tokio::test]
+    async fn test_create_variable_rate_limited() {
+        const REQUESTS_PER_SECOND: u64 = 10;
+        const VARIABLES: usize = 25;
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v2/workspaces/ws-1/vars"))
+            .respond_with(EchoVariable)
+            .mount(&server)
+            .await;
+
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from("token"),
+        ))
+        .unwrap()
+        .with_rate_limiter(RateLimiter::new(REQUESTS_PER_SECOND).unwrap());
+
+        let vars: Vec<TerraformVariableProperty> = (0..VARIABLES)
+            .map(|i| TerraformVariableProperty::new(None, format!("key_{}", i), None, json!(i)))
+            .collect();
+        let start = std::time::Instant::now();
+        let res = client
+            .create_variable(&VariableTarget::Workspace(String::from("ws-1")), &vars)
+            .await
+            .unwrap();
+
+        // Throttled rather than dropped: 10 as the burst, then 10 and 5 in the next seconds
+        assert!(std::time::Duration::from_millis(1900) <= start.elapsed());
+        assert_eq!(res.len(), VARIABLES);
+        let sent_keys: Vec<String> = server
+            .received_requests()
+            .await
             .unwrap()
-            .get_variable_description()
-        {
-            Some(val) => val,
-            None => "",
-        };
+            .iter()
+            .map(|req| {
+                req.body_json::<serde_json::Value>().unwrap()["data"]["attributes"]["key"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        let expected_keys: Vec<String> = (0..VARIABLES).map(|i| format!("key_{}", i)).collect();
+        assert_eq!(sent_keys, expected_keys);
+    }
 
-        let data_value = if is_string {
-            terraform_variable_property
-                .get(i)
-                .unwrap()
-                .get_value()
-                .as_str()
-                .unwrap()
-                .to_string()
-        } else {
-            terraform_variable_property
-                .get(i)
-                .unwrap()
-                .get_value()
-                .to_string()
-        };
+    #[tokio::test]
+    async fn test_create_update_variable_mock() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization("org");
+        let workspace_id = server.add_workspace("org", "workspace", None);
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from(MOCK_TOKEN),
+        ))
+        .unwrap();
 
-        let data = json!({
-            "data":{
-                "type": "vars",
-                "attributes": {
-                    "key": terraform_variable_property.get(i).unwrap().get_variable_name(),
-                    "value": data_value,
-                    "description": description,
-                    "category": "terraform",
-                    "hcl": is_hcl
-                  }
-              }
-        });
-
-        let response = reqwest::Client::new()
-            .post(url.as_str())
-            .header("Authorization", format!("Bearer {}", token))
-            .header("Content-Type", "application/vnd.api+json")
-            .body(data.to_string())
-            .send()
-            .await?;
+        let cases: Vec<(&str, serde_json::Value, bool)> = vec![
+            ("string", json!("aaa\"bbb"), false),
+            ("number", json!(-1.2345), false),
+            ("list", json!(["aaa", "bbb"]), true),
+            ("object", json!({"a": "aaa", "b": null}), true),
+        ];
+        let properties: Vec<TerraformVariableProperty> = cases
+            .iter()
+            .map(|(key, value, _)| {
+                TerraformVariableProperty::new(
+                    None,
+                    key.to_string(),
+                    Some(format!("{} description", key)),
+                    value.clone(),
+                )
+            })
+            .collect();
+
+        // Create
+        let res = client
+            .create_variable(
+                &VariableTarget::Workspace(workspace_id.to_string()),
+                &properties,
+            )
+            .await
+            .unwrap();
+        for ((key, value, is_hcl), created) in cases.iter().zip(res.iter()) {
+            assert_eq!(created.get_variable_name(), *key);
+            assert_eq!(created.get_value(), value);
+            assert_eq!(
+                created.get_variable_description(),
+                format!("{} description", key)
+            );
+            assert_eq!(
+                server
+                    .variable(&workspace_id, key, VariableCategory::Terraform)
+                    .unwrap()["hcl"],
+                json!(is_hcl)
+            );
+        }
 
-        assert!(
-            response.status() == 201,
-            "Response status is {}.",
-            response.status()
-        );
+        // Update
+        let existing_variables = client
+            .get_variables(&VariableTarget::Workspace(workspace_id.to_string()))
+            .await
+            .unwrap();
+        let status = variable_status(&existing_variables, &[(
+            String::from("string"),
+            VariableCategory::Terraform,
+        )]);
+        let variable_id = status.first().unwrap().get_variable_id().clone().unwrap();
+        let res = client
+            .update_variable(&VariableTarget::Workspace(workspace_id.to_string()), &[
+                TerraformVariableProperty::new(
+                    Some(variable_id.clone()),
+                    String::from("string"),
+                    None,
+                    json!("updated_val"),
+                ),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(res.first().unwrap().get_value(), &json!("updated_val"));
+        assert_eq!(res.first().unwrap().get_variable_description(), "");
 
-        let json_value: serde_json::Value = serde_json::from_str(&response.text().await.unwrap())?;
-        let value = if is_string {
-            json_value["data"]["attributes"]["value"].clone()
-        } else {
-            serde_json::from_str::<serde_json::Value>(
-                json_value["data"]["attributes"]["value"].as_str().unwrap(),
-            )
-            .unwrap()
-        };
-        result.push(TerraformVariableRegistrationResult {
-            variable_id: json_value["data"]["id"].as_str().unwrap().to_string(),
-            variable_name: json_value["data"]["attributes"]["key"]
-                .as_str()
-                .unwrap()
-                .to_string(),
-            variable_description: json_value["data"]["attributes"]["description"]
-                .as_str()
-                .unwrap()
-                .to_string(),
-            value,
-        });
+        // Delete
+        client
+            .delete_variable(&VariableTarget::Workspace(workspace_id.to_string()), &[
+                variable_id,
+            ])
+            .await
+            .unwrap();
+        assert_eq!(server.variable_keys(&workspace_id), vec![
+            "list", "number", "object"
+        ]);
     }
 
-    log::info!("{} Variable(s) successfully created.", count);
+    #[test]
+    fn test_value_encoding() {
+        let outputs: HashMap<String, serde_json::Value> = get_outputs("files/test/outputs.json")
+            .unwrap()
+            .into_iter()
+            .map(|val| (val.get_name().to_owned(), val.get_value().to_owned()))
+            .collect();
+
+        // `(output name, [auto, hcl, string, json-string])` with `(value, is_hcl)` of each mode
+        let object = r#"{"a":"aaa","b":"bbb","c":null}"#;
+        let map = r#"{"a":"aaa","b":"bbb","c":"ccc"}"#;
+        let set = r#"[{"name":"aaa","type":"bbb"}]"#;
+        let tuple = r#"["aaa","bbb"]"#;
+        let cases: Vec<(&str, [(&str, bool); 4])> = vec![
+            ("bool", [
+                ("false", false),
+                ("false", true),
+                ("false", false),
+                ("false", false),
+            ]),
+            ("list_of_object", [
+                (object, true),
+                (object, true),
+                (object, false),
+                (object, false),
+            ]),
+            ("map_of_string", [
+                (map, true),
+                (map, true),
+                (map, false),
+                (map, false),
+            ]),
+            ("number_0", [
+                ("0", false),
+                ("0", true),
+                ("0", false),
+                ("0", false),
+            ]),
+            ("number_float", [
+                ("1.2345", false),
+                ("1.2345", true),
+                ("1.2345", false),
+                ("1.2345", false),
+            ]),
+            ("number_negative", [
+                ("-1.2345", false),
+                ("-1.2345", true),
+                ("-1.2345", false),
+                ("-1.2345", false),
+            ]),
+            ("set_of_object", [
+                (set, true),
+                (set, true),
+                (set, false),
+                (set, false),
+            ]),
+            ("string", [
+                ("aaa", false),
+                (r#""aaa""#, true),
+                ("aaa", false),
+                (r#""aaa""#, false),
+            ]),
+            ("string_with_quote", [
+                (r#"aaa"bbb"#, false),
+                (r#""aaa\"bbb""#, true),
+                (r#"aaa"bbb"#, false),
+                (r#""aaa\"bbb""#, false),
+            ]),
+            ("tuple", [
+                (tuple, true),
+                (tuple, true),
+                (tuple, false),
+                (tuple, false),
+            ]),
+        ];
+        let encodings = [
+            ValueEncoding::Auto,
+            ValueEncoding::Hcl,
+            ValueEncoding::String,
+            ValueEncoding::JsonString,
+        ];
 
-    Ok(result)
-}
+        for (output_name, expected) in cases.iter() {
+            let value = outputs.get(*output_name).unwrap();
+            for (encoding, (data_value, is_hcl)) in encodings.iter().zip(expected.iter()) {
+                assert_eq!(
+                    encoding.encode(value),
+                    (data_value.to_string(), *is_hcl),
+                    "`{}` encoded as {:?}",
+                    output_name,
+                    encoding
+                );
+            }
+        }
 
-#[cfg(test)]
-pub mod tests {
+        assert_eq!(
+            ValueEncoding::RawHcl.encode(&json!("{ a = 1 }")),
+            (String::from("{ a = 1 }"), true)
+        );
 
-    use super::*;
-    use crate::terraform_api::check_variable_status::check_variable_status;
+        assert_eq!("json-string".parse(), Ok(ValueEncoding::JsonString));
+        assert!("yaml".parse::<ValueEncoding>().is_err());
+        assert!("raw-hcl".parse::<ValueEncoding>().is_err());
+    }
 
-    // Function for deleting test data
-    // Call on demand.
-    pub async fn delete_variable(
-        api_conn_prop: &TerraformApiConnectionProperty,
-        variable_ids: &Vec<String>,
-        workspace_id: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut url = api_conn_prop.base_url().clone();
-        let token = api_conn_prop.token();
-
-        // Limit the rate 20 requests per second.
-        let ratelimiter = ratelimit::Ratelimiter::builder(20, std::time::Duration::from_secs(1))
-            .max_tokens(20)
-            .initial_available(20)
-            .build()
-            .unwrap();
+    #[test]
+    fn test_is_up_to_date() {
+        let existing = |value: Option<&str>, hcl: bool, sensitive: bool| -> VarAttributes {
+            serde_json::from_value(json!({
+                "key": "key",
+                "value": value,
+                "description": "description",
+                "category": "terraform",
+                "hcl": hcl,
+                "sensitive": sensitive
+            }))
+            .unwrap()
+        };
+        let property = |value: serde_json::Value, encoding: ValueEncoding| {
+            TerraformVariableProperty::new(
+                Some(String::from("var-1")),
+                String::from("key"),
+                Some(String::from("description")),
+                value,
+            )
+            .with_encoding(encoding)
+        };
 
-        let count = variable_ids.len();
-        for i in 0..count {
-            if let Err(sleep) = ratelimiter.try_wait() {
-                std::thread::sleep(sleep);
-                continue;
-            }
+        // `(property, existing, is_up_to_date)`
+        let cases = vec![
+            (
+                property(json!("aaa"), ValueEncoding::Auto),
+                existing(Some("aaa"), false, false),
+                true,
+            ),
+            // Raw strings are compared as they are.
+            (
+                property(json!("aaa"), ValueEncoding::Auto),
+                existing(Some("aaa "), false, false),
+                false,
+            ),
+            (
+                property(json!(-1.2345), ValueEncoding::Auto),
+                existing(Some("-1.2345"), false, false),
+                true,
+            ),
+            // HCL of another format
+            (
+                property(json!([{"name": "aaa", "type": "bbb"}]), ValueEncoding::Auto),
+                existing(
+                    Some("[\n  {\n    type = \"bbb\"\n    name = \"aaa\"\n  },\n]"),
+                    true,
+                    false,
+                ),
+                true,
+            ),
+            (
+                property(json!({"a": "aaa"}), ValueEncoding::Auto),
+                existing(Some(r#"{ a = "bbb" }"#), true, false),
+                false,
+            ),
+            // JSON of another order of keys
+            (
+                property(json!({"a": "aaa", "b": 1}), ValueEncoding::JsonString),
+                existing(Some(r#"{"b": 1, "a": "aaa"}"#), false, false),
+                true,
+            ),
+            // HCL flag differs
+            (
+                property(json!(0), ValueEncoding::Hcl),
+                existing(Some("0"), false, false),
+                false,
+            ),
+            // Sensitive values cannot be compared.
+            (
+                property(json!("aaa"), ValueEncoding::Auto).with_sensitive(true),
+                existing(None, false, true),
+                false,
+            ),
+            (
+                property(json!("aaa"), ValueEncoding::Auto),
+                existing(None, false, true),
+                false,
+            ),
+        ];
+        for (i, (property, existing, expected)) in cases.iter().enumerate() {
+            assert_eq!(property.is_up_to_date(existing), *expected, "case {}", i);
+        }
 
-            let variable_id = &variable_ids.get(i).expect("Failed to get variable_id.");
-            let path = format!("/api/v2/workspaces/{}/vars/{}", workspace_id, variable_id);
-            url.set_path(&path);
+        // Description differs
+        let property = TerraformVariableProperty::new(
+            Some(String::from("var-1")),
+            String::from("key"),
+            None,
+            json!("aaa"),
+        );
+        assert!(!property.is_up_to_date(&existing(Some("aaa"), false, false)));
+    }
 
-            let response = reqwest::Client::new()
-                .delete(url.as_str())
-                .header("Authorization", format!("Bearer {}", token))
-                .header("Content-Type", "application/vnd.api+json")
-                .send()
-                .await?;
+    #[tokio::test]
+    async fn test_create_variable_encoding_mock() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization("org");
+        let workspace_id = server.add_workspace("org", "workspace", None);
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from(MOCK_TOKEN),
+        ))
+        .unwrap();
 
-            assert!(
-                response.status() == 204,
-                "Response status is {}.",
-                response.status()
-            );
+        let cases: Vec<(&str, serde_json::Value, ValueEncoding, &str, bool)> = vec![
+            ("auto", json!("aaa"), ValueEncoding::Auto, "aaa", false),
+            ("hcl", json!(1.2345), ValueEncoding::Hcl, "1.2345", true),
+            (
+                "string",
+                json!(["aaa"]),
+                ValueEncoding::String,
+                r#"["aaa"]"#,
+                false,
+            ),
+            (
+                "json_string",
+                json!({"a": "aaa"}),
+                ValueEncoding::JsonString,
+                r#"{"a":"aaa"}"#,
+                false,
+            ),
+        ];
+        let properties: Vec<TerraformVariableProperty> = cases
+            .iter()
+            .map(|(key, value, encoding, ..)| {
+                TerraformVariableProperty::new(None, key.to_string(), None, value.clone())
+                    .with_encoding(*encoding)
+            })
+            .collect();
+
+        let res = client
+            .create_variable(
+                &VariableTarget::Workspace(workspace_id.to_string()),
+                &properties,
+            )
+            .await
+            .unwrap();
+        for ((key, value, _, data_value, is_hcl), created) in cases.iter().zip(res.iter()) {
+            // The value registered is decoded back
+            assert_eq!(created.get_value(), value);
+            let variable = server
+                .variable(&workspace_id, key, VariableCategory::Terraform)
+                .unwrap();
+            assert_eq!(variable["value"], json!(data_value));
+            assert_eq!(variable["hcl"], json!(is_hcl));
+        }
+    }
 
-            println!("Temporarily created variable deleted: {}.", variable_id);
+    #[tokio::test]
+    #[ignore = "Requires the cassette recorded with `TFVE_CASSETTE_MODE=record`"]
+    async fn test_create_update_variable_cassette() {
+        let cassette = std::sync::Arc::new(Cassette::new("create_update_variable"));
+        let client = cassette.client();
+        let workspace_id = cassette.env("TFVE_WORKSPACE_ID_TESTING", "ws-testing");
+
+        let cases: Vec<(&str, serde_json::Value, Option<String>)> = vec![
+            (
+                "tfve_cassette_string",
+                json!("aaa"),
+                Some(String::from("string description")),
+            ),
+            ("tfve_cassette_number", json!(-1.2345), None),
+            ("tfve_cassette_list", json!(["aaa", "bbb"]), None),
+        ];
+        let properties: Vec<TerraformVariableProperty> = cases
+            .iter()
+            .map(|(key, value, description)| {
+                TerraformVariableProperty::new(
+                    None,
+                    key.to_string(),
+                    description.clone(),
+                    value.clone(),
+                )
+            })
+            .collect();
+
+        // Create
+        let res = client
+            .create_variable(
+                &VariableTarget::Workspace(workspace_id.to_string()),
+                &properties,
+            )
+            .await
+            .unwrap();
+        for ((key, value, description), created) in cases.iter().zip(res.iter()) {
+            assert_eq!(created.get_variable_name(), *key);
+            assert_eq!(created.get_value(), value);
+            assert_eq!(
+                created.get_variable_description(),
+                description.clone().unwrap_or_default()
+            );
         }
 
-        Ok(())
+        // Update
+        let names: Vec<(String, VariableCategory)> = cases
+            .iter()
+            .map(|(key, _, _)| (key.to_string(), VariableCategory::Terraform))
+            .collect();
+        let existing_variables = client
+            .get_variables(&VariableTarget::Workspace(workspace_id.to_string()))
+            .await
+            .unwrap();
+        let status = variable_status(&existing_variables, &names);
+        let variable_ids: Vec<String> = status
+            .iter()
+            .map(|val| val.get_variable_id().clone().unwrap())
+            .collect();
+        let res = client
+            .update_variable(&VariableTarget::Workspace(workspace_id.to_string()), &[
+                TerraformVariableProperty::new(
+                    variable_ids.first().cloned(),
+                    names.first().unwrap().0.to_owned(),
+                    None,
+                    json!("updated_val"),
+                ),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(res.first().unwrap().get_value(), &json!("updated_val"));
+
+        // Delete test data
+        client
+            .delete_variable(
+                &VariableTarget::Workspace(workspace_id.to_string()),
+                &variable_ids,
+            )
+            .await
+            .unwrap();
+
+        cassette.finish();
     }
 
     #[tokio::test]
     #[ignore = "Requires API access"]
     async fn test_update_variable() {
-        let api_conn_prop = TerraformApiConnectionProperty::new(
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
             url::Url::parse("https://app.terraform.io").unwrap(),
             std::env::var("TFVE_TOKEN").unwrap(),
-        );
+        ))
+        .unwrap();
 
         let cases: Vec<serde_json::Value> = vec![
             json!("aaa"),   // string
@@ -427,59 +1057,58 @@ pub mod tests {
             for case in cases.iter() {
                 let test_val = uuid::Uuid::new_v4().to_string();
                 // Create temporary variable to be updated
-                let res = create_variable(&workspace_id, &api_conn_prop, &vec![
-                    TerraformVariableProperty {
-                        variable_id: None,
-                        variable_name: test_val.to_owned(),
-                        variable_description: None,
-                        value: case.clone(),
-                    },
-                ])
-                .await
-                .unwrap();
-
-                let status = check_variable_status(&workspace_id, &api_conn_prop, &vec![res
-                    .get(0)
-                    .unwrap()
-                    .get_variable_name()
-                    .to_owned()
-                    .clone()])
-                .await
-                .unwrap();
+                let res = client
+                    .create_variable(&VariableTarget::Workspace(workspace_id.to_string()), &[
+                        TerraformVariableProperty::new(
+                            None,
+                            test_val.to_owned(),
+                            None,
+                            case.clone(),
+                        ),
+                    ])
+                    .await
+                    .unwrap();
+
+                let existing_variables = client
+                    .get_variables(&VariableTarget::Workspace(workspace_id.to_string()))
+                    .await
+                    .unwrap();
+                let status = variable_status(&existing_variables, &[(
+                    res.first().unwrap().get_variable_name().to_owned(),
+                    VariableCategory::Terraform,
+                )]);
 
                 // Exec update
-                let res_update = update_variable(&workspace_id, &api_conn_prop, &vec![
-                    TerraformVariableProperty {
-                        variable_id: Some(
-                            status.get(0).unwrap().get_variable_id().clone().unwrap(),
+                let res_update = client
+                    .update_variable(&VariableTarget::Workspace(workspace_id.to_string()), &[
+                        TerraformVariableProperty::new(
+                            Some(status.first().unwrap().get_variable_id().clone().unwrap()),
+                            test_val.to_owned(),
+                            Some(test_val.to_owned()),
+                            json!("updated_val"),
                         ),
-                        variable_name: test_val.to_owned(),
-                        variable_description: Some(test_val.to_owned()),
-                        value: json!("updated_val"),
-                    },
-                ])
-                .await
-                .unwrap();
+                    ])
+                    .await
+                    .unwrap();
 
                 // Value
                 assert_eq!(
                     json!("updated_val"),
-                    res_update.get(0).unwrap().get_value().to_owned()
+                    res_update.first().unwrap().get_value().to_owned()
                 );
                 // Description
                 assert_eq!(
                     test_val,
-                    res_update.get(0).unwrap().get_variable_description()
+                    res_update.first().unwrap().get_variable_description()
                 );
 
                 // Delete test data
-                delete_variable(
-                    &api_conn_prop,
-                    &vec![status.get(0).unwrap().get_variable_id().clone().unwrap()],
-                    &workspace_id,
-                )
-                .await
-                .unwrap();
+                client
+                    .delete_variable(&VariableTarget::Workspace(workspace_id.to_string()), &[
+                        status.first().unwrap().get_variable_id().clone().unwrap(),
+                    ])
+                    .await
+                    .unwrap();
             }
         }
     }
@@ -487,10 +1116,11 @@ pub mod tests {
     #[tokio::test]
     #[ignore = "Requires API access"]
     async fn test_create_variable_with_description_short() {
-        let api_conn_prop = TerraformApiConnectionProperty::new(
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
             url::Url::parse("https://app.terraform.io").unwrap(),
             std::env::var("TFVE_TOKEN").unwrap(),
-        );
+        ))
+        .unwrap();
 
         let workspace_id = &std::env::var("TFVE_WORKSPACE_ID_TESTING")
             .expect("Environment variable `TFVE_WORKSPACE_ID_TESTING` required.");
@@ -506,45 +1136,51 @@ pub mod tests {
         // Iterates over cases
         for case in cases.iter() {
             let test_val = uuid::Uuid::new_v4().to_string();
-            let res = create_variable(workspace_id, &api_conn_prop, &vec![
-                TerraformVariableProperty {
-                    variable_id: None,
-                    variable_name: test_val.to_owned(),
-                    variable_description: Some(test_val.to_owned()),
-                    value: case.clone(),
-                },
-            ])
-            .await
-            .unwrap();
+            let res = client
+                .create_variable(&VariableTarget::Workspace(workspace_id.to_string()), &[
+                    TerraformVariableProperty::new(
+                        None,
+                        test_val.to_owned(),
+                        Some(test_val.to_owned()),
+                        case.clone(),
+                    ),
+                ])
+                .await
+                .unwrap();
 
-            let status = check_variable_status(workspace_id, &api_conn_prop, &vec![res
-                .get(0)
-                .unwrap()
-                .variable_name
-                .clone()])
-            .await
-            .unwrap();
+            let existing_variables = client
+                .get_variables(&VariableTarget::Workspace(workspace_id.to_string()))
+                .await
+                .unwrap();
+            let status = variable_status(&existing_variables, &[(
+                res.first().unwrap().variable_name.clone(),
+                VariableCategory::Terraform,
+            )]);
 
             // Variable ID should be Some
-            assert!(status.get(0).unwrap().get_variable_id().is_some());
+            assert!(status.first().unwrap().get_variable_id().is_some());
             // Value
             assert_eq!(
                 &serde_json::from_str::<serde_json::Value>(
-                    &res.get(0).unwrap().get_value().to_string()
+                    &res.first().unwrap().get_value().to_string()
                 )
                 .unwrap(),
                 case
             );
             // Description
             assert_eq!(
-                res.get(0).unwrap().get_variable_description().to_owned(),
+                res.first().unwrap().get_variable_description().to_owned(),
                 test_val
             );
 
-            variable_ids.push(status.get(0).unwrap().get_variable_id().clone().unwrap());
+            variable_ids.push(status.first().unwrap().get_variable_id().clone().unwrap());
         }
         // Delete test data
-        delete_variable(&api_conn_prop, &variable_ids, workspace_id)
+        client
+            .delete_variable(
+                &VariableTarget::Workspace(workspace_id.to_string()),
+                &variable_ids,
+            )
             .await
             .unwrap();
     }
@@ -552,10 +1188,11 @@ pub mod tests {
     #[tokio::test]
     #[ignore = "Requires API access"]
     async fn test_create_variable_without_description_short() {
-        let api_conn_prop = TerraformApiConnectionProperty::new(
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
             url::Url::parse("https://app.terraform.io").unwrap(),
             std::env::var("TFVE_TOKEN").unwrap(),
-        );
+        ))
+        .unwrap();
 
         let workspace_id = &std::env::var("TFVE_WORKSPACE_ID_TESTING")
             .expect("Environment variable `TFVE_WORKSPACE_ID_TESTING` required.");
@@ -570,45 +1207,46 @@ pub mod tests {
         // Iterates over cases
         for case in cases.iter() {
             let test_val = uuid::Uuid::new_v4().to_string();
-            let res = create_variable(workspace_id, &api_conn_prop, &vec![
-                TerraformVariableProperty {
-                    variable_id: None,
-                    variable_name: test_val.to_owned(),
-                    variable_description: None,
-                    value: case.clone(),
-                },
-            ])
-            .await
-            .unwrap();
+            let res = client
+                .create_variable(&VariableTarget::Workspace(workspace_id.to_string()), &[
+                    TerraformVariableProperty::new(None, test_val.to_owned(), None, case.clone()),
+                ])
+                .await
+                .unwrap();
 
-            let status = check_variable_status(workspace_id, &api_conn_prop, &vec![res
-                .get(0)
-                .unwrap()
-                .variable_name
-                .clone()])
-            .await
-            .unwrap();
+            let existing_variables = client
+                .get_variables(&VariableTarget::Workspace(workspace_id.to_string()))
+                .await
+                .unwrap();
+            let status = variable_status(&existing_variables, &[(
+                res.first().unwrap().variable_name.clone(),
+                VariableCategory::Terraform,
+            )]);
 
             // Variable ID should be Some
-            assert!(status.get(0).unwrap().get_variable_id().is_some());
+            assert!(status.first().unwrap().get_variable_id().is_some());
             // Value
             assert_eq!(
                 &serde_json::from_str::<serde_json::Value>(
-                    &res.get(0).unwrap().get_value().to_string()
+                    &res.first().unwrap().get_value().to_string()
                 )
                 .unwrap(),
                 case
             );
             // Description
             assert_eq!(
-                res.get(0).unwrap().get_variable_description().to_owned(),
+                res.first().unwrap().get_variable_description().to_owned(),
                 ""
             );
 
-            variable_ids.push(status.get(0).unwrap().get_variable_id().clone().unwrap());
+            variable_ids.push(status.first().unwrap().get_variable_id().clone().unwrap());
         }
         // Delete test data
-        delete_variable(&api_conn_prop, &variable_ids, workspace_id)
+        client
+            .delete_variable(
+                &VariableTarget::Workspace(workspace_id.to_string()),
+                &variable_ids,
+            )
             .await
             .unwrap();
     }
@@ -616,10 +1254,11 @@ pub mod tests {
     #[tokio::test]
     #[ignore = "Requires API access, Takes a little long time"]
     async fn test_create_variable_full() {
-        let api_conn_prop = TerraformApiConnectionProperty::new(
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
             url::Url::parse("https://app.terraform.io").unwrap(),
             std::env::var("TFVE_TOKEN").unwrap(),
-        );
+        ))
+        .unwrap();
 
         let workspace_id = &std::env::var("TFVE_WORKSPACE_ID_TESTING")
             .expect("Environment variable `TFVE_WORKSPACE_ID_TESTING` required.");
@@ -643,44 +1282,49 @@ pub mod tests {
         // Iterates over cases
         for case in cases.iter() {
             let test_val = uuid::Uuid::new_v4().to_string();
-            let res = create_variable(workspace_id, &api_conn_prop, &vec![
-                TerraformVariableProperty {
-                    variable_id: None,
-                    variable_name: test_val.to_owned(),
-                    variable_description: Some(test_val.to_owned()),
-                    value: case.clone(),
-                },
-            ])
-            .await
-            .unwrap();
+            let res = client
+                .create_variable(&VariableTarget::Workspace(workspace_id.to_string()), &[
+                    TerraformVariableProperty::new(
+                        None,
+                        test_val.to_owned(),
+                        Some(test_val.to_owned()),
+                        case.clone(),
+                    ),
+                ])
+                .await
+                .unwrap();
 
-            let status = check_variable_status(workspace_id, &api_conn_prop, &vec![res
-                .get(0)
-                .unwrap()
-                .get_variable_name()
-                .to_owned()
-                .clone()])
-            .await
-            .unwrap();
+            let existing_variables = client
+                .get_variables(&VariableTarget::Workspace(workspace_id.to_string()))
+                .await
+                .unwrap();
+            let status = variable_status(&existing_variables, &[(
+                res.first().unwrap().get_variable_name().to_owned(),
+                VariableCategory::Terraform,
+            )]);
 
             // Variable ID should be Some
-            assert!(status.get(0).unwrap().get_variable_id().is_some());
+            assert!(status.first().unwrap().get_variable_id().is_some());
             // Value
             assert_eq!(
-                &serde_json::from_str::<serde_json::Value>(&res.get(0).unwrap().value.to_string())
+                &serde_json::from_str::<serde_json::Value>(&res.first().unwrap().value.to_string())
                     .unwrap(),
                 case
             );
             // Description
             assert_eq!(
-                res.get(0).unwrap().get_variable_description().to_owned(),
+                res.first().unwrap().get_variable_description().to_owned(),
                 test_val
             );
 
-            variable_ids.push(status.get(0).unwrap().get_variable_id().clone().unwrap());
+            variable_ids.push(status.first().unwrap().get_variable_id().clone().unwrap());
         }
         // Delete test data
-        delete_variable(&api_conn_prop, &variable_ids, workspace_id)
+        client
+            .delete_variable(
+                &VariableTarget::Workspace(workspace_id.to_string()),
+                &variable_ids,
+            )
             .await
             .unwrap();
     }
@@ -0,0 +1,169 @@
+//! Retry policy for rate-limited and failed requests.
+//!
+//! **API Reference:** https://developer.hashicorp.com/terraform/cloud-docs/api-docs#rate-limiting
+
+use rand::Rng;
+use reqwest::{header::HeaderMap, Method, StatusCode};
+
+/// Default max number of attempts including the first one
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+/// Default delay before the first retry
+const DEFAULT_BASE_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Upper bound of a delay between attempts
+const DEFAULT_MAX_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Retry policy with exponential backoff and full jitter
+///
+/// Responses with `429 Too Many Requests` and failures to connect are retried, and so are `5xx` and
+/// timeouts except for `POST`, which may have created the resource before failing. A delay given
+/// by the server with `Retry-After` or `X-RateLimit-Reset` is honored in preference to the backoff.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: std::time::Duration,
+    max_delay: std::time::Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ATTEMPTS)
+    }
+}
+
+impl RetryPolicy {
+    /// `max_attempts` includes the first attempt, so `1` disables retries.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_base_delay(mut self, base_delay: std::time::Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Whether a response with `status` to a `method` request should be retried.
+    ///
+    /// `429` is returned before the request is processed, so it is safe to retry any request.
+    /// `5xx` is retried only for idempotent ones, as retrying `POST` may create duplicates.
+    pub fn is_retryable(method: &Method, status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS
+            || (status.is_server_error() && method != Method::POST)
+    }
+
+    /// Whether a request with `method` that failed with transport error `err` should be retried.
+    ///
+    /// A request that failed to connect was never sent, so it is safe to retry any request. A
+    /// timed out request may have been processed, so it is retried only for idempotent ones.
+    pub fn is_retryable_error(method: &Method, err: &reqwest::Error) -> bool {
+        err.is_connect() || (err.is_timeout() && method != Method::POST)
+    }
+
+    /// Delay before the next attempt after `attempt` (starting from 1) failed.
+    pub fn delay(&self, attempt: u32, headers: &HeaderMap) -> std::time::Duration {
+        if let Some(delay) = server_delay(headers) {
+            return delay.min(self.max_delay);
+        }
+
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let jitter_ms = rand::thread_rng().gen_range(0..=backoff.as_millis() as u64);
+
+        std::time::Duration::from_millis(jitter_ms)
+    }
+}
+
+/// Read the delay requested by the server from `Retry-After` or `X-RateLimit-Reset`.
+///
+/// Both are read as seconds; HCP Terraform sends `X-RateLimit-Reset` with a fraction.
+fn server_delay(headers: &HeaderMap) -> Option<std::time::Duration> {
+    ["retry-after", "x-ratelimit-reset"]
+        .iter()
+        .filter_map(|name| headers.get(*name))
+        .filter_map(|val| val.to_str().ok())
+        .filter_map(|val| val.trim().parse::<f64>().ok())
+        .find(|secs| secs.is_finite() && 0.0 <= *secs)
+        .map(std::time::Duration::from_secs_f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_is_retryable() {
+        for method in [Method::GET, Method::POST, Method::PATCH, Method::DELETE] {
+            assert!(RetryPolicy::is_retryable(
+                &method,
+                StatusCode::TOO_MANY_REQUESTS
+            ));
+            assert!(!RetryPolicy::is_retryable(&method, StatusCode::NOT_FOUND));
+            assert!(!RetryPolicy::is_retryable(
+                &method,
+                StatusCode::UNPROCESSABLE_ENTITY
+            ));
+        }
+
+        for method in [Method::GET, Method::PATCH, Method::DELETE] {
+            assert!(RetryPolicy::is_retryable(&method, StatusCode::BAD_GATEWAY));
+            assert!(RetryPolicy::is_retryable(
+                &method,
+                StatusCode::SERVICE_UNAVAILABLE
+            ));
+        }
+        // A failed `POST` may have created the resource.
+        assert!(!RetryPolicy::is_retryable(
+            &Method::POST,
+            StatusCode::BAD_GATEWAY
+        ));
+        assert!(!RetryPolicy::is_retryable(
+            &Method::POST,
+            StatusCode::SERVICE_UNAVAILABLE
+        ));
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy::new(5);
+
+        // Exponential backoff with jitter
+        let headers = HeaderMap::new();
+        for attempt in 1..=10 {
+            let delay = policy.delay(attempt, &headers);
+            assert!(delay <= DEFAULT_BASE_DELAY * 2u32.pow(attempt - 1));
+            assert!(delay <= DEFAULT_MAX_DELAY);
+        }
+
+        // `Retry-After`
+        let mut headers = HeaderMap::new();
+        headers.insert("Retry-After", HeaderValue::from_static("3"));
+        assert_eq!(policy.delay(1, &headers), std::time::Duration::from_secs(3));
+
+        // `X-RateLimit-Reset`
+        let mut headers = HeaderMap::new();
+        headers.insert("X-RateLimit-Reset", HeaderValue::from_static("0.25"));
+        assert_eq!(
+            policy.delay(1, &headers),
+            std::time::Duration::from_millis(250)
+        );
+
+        // Capped
+        let mut headers = HeaderMap::new();
+        headers.insert("Retry-After", HeaderValue::from_static("3600"));
+        assert_eq!(policy.delay(1, &headers), DEFAULT_MAX_DELAY);
+    }
+}
@@ -0,0 +1,207 @@
+//! Find, create and apply Terraform Cloud variable sets.
+//!
+//! **API Reference:** https://developer.hashicorp.com/terraform/cloud-docs/api-docs/variable-sets
+
+use serde_json::json;
+
+use crate::{
+    error::TfvarExportError,
+    terraform_api::{
+        client::TerraformClient,
+        models::{Document, Resource, VarsetAttributes},
+    },
+};
+
+impl TerraformClient {
+    /// Get the variable set `name` with the `workspaces` and `projects` it is applied to, or
+    /// `None` if the organization has no such one.
+    pub async fn get_varset(
+        &self,
+        organization_name: &str,
+        name: &str,
+    ) -> Result<Option<Resource<VarsetAttributes>>, TfvarExportError> {
+        let path = format!("/api/v2/organizations/{}/varsets", organization_name);
+
+        log::info!(
+            "Getting variable set(s) from the organization {}.",
+            organization_name
+        );
+
+        let varset = self
+            .get_all_pages::<VarsetAttributes>(&path)
+            .await?
+            .into_iter()
+            .find(|val| val.get_attributes().get_name() == name);
+
+        Ok(varset)
+    }
+
+    /// Get the ID of the variable set `name`, or `None` if the organization has no such one.
+    pub async fn get_varset_id(
+        &self,
+        organization_name: &str,
+        name: &str,
+    ) -> Result<Option<String>, TfvarExportError> {
+        let varset = self.get_varset(organization_name, name).await?;
+
+        Ok(varset.map(|val| val.get_id().to_string()))
+    }
+
+    /// Create the variable set `name`, which is not applied globally, and return its ID.
+    pub async fn create_varset(
+        &self,
+        organization_name: &str,
+        name: &str,
+    ) -> Result<String, TfvarExportError> {
+        let path = format!("/api/v2/organizations/{}/varsets", organization_name);
+        let data = json!({
+            "data": {
+                "type": "varsets",
+                "attributes": {
+                    "name": name,
+                    "global": false
+                }
+            }
+        });
+
+        let json_value: Document<Resource<VarsetAttributes>> = self
+            .send_json(
+                reqwest::Method::POST,
+                self.url(&path),
+                Some(&data),
+                reqwest::StatusCode::CREATED,
+            )
+            .await?;
+        let varset_id = json_value.into_data().get_id().to_string();
+
+        log::info!("Variable set `{}` created as {}.", name, varset_id);
+
+        Ok(varset_id)
+    }
+
+    /// Apply a variable set to workspaces.
+    pub async fn apply_varset_to_workspaces(
+        &self,
+        varset_id: &str,
+        workspace_ids: &[String],
+    ) -> Result<(), TfvarExportError> {
+        self.apply_varset(varset_id, "workspaces", workspace_ids)
+            .await
+    }
+
+    /// Apply a variable set to projects.
+    pub async fn apply_varset_to_projects(
+        &self,
+        varset_id: &str,
+        project_ids: &[String],
+    ) -> Result<(), TfvarExportError> {
+        self.apply_varset(varset_id, "projects", project_ids).await
+    }
+
+    /// Add `resource_type` resources to the relationship of the same name of a variable set.
+    ///
+    /// Resources the variable set is already applied to are left as they are.
+    async fn apply_varset(
+        &self,
+        varset_id: &str,
+        resource_type: &str,
+        ids: &[String],
+    ) -> Result<(), TfvarExportError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let path = format!(
+            "/api/v2/varsets/{}/relationships/{}",
+            varset_id, resource_type
+        );
+        let data: Vec<serde_json::Value> = ids
+            .iter()
+            .map(|id| json!({"type": resource_type, "id": id}))
+            .collect();
+
+        self.send(
+            reqwest::Method::POST,
+            self.url(&path),
+            Some(&json!({ "data": data })),
+            reqwest::StatusCode::NO_CONTENT,
+        )
+        .await?;
+
+        log::info!(
+            "Variable set {} applied to {} {}.",
+            varset_id,
+            ids.len(),
+            resource_type
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terraform_api::{
+        connection_prop::TerraformApiConnectionProperty,
+        mock_server::{MockTerraformServer, MOCK_TOKEN},
+    };
+
+    #[tokio::test]
+    async fn test_varset_mock() {
+        let server = MockTerraformServer::start().await;
+        server.add_organization("org");
+        let project_id = server.add_project("org", "project");
+        let workspace_id = server.add_workspace("org", "workspace", None);
+        let existing_id = server.add_variable_set("org", "existing");
+        let client = TerraformClient::new(&TerraformApiConnectionProperty::new(
+            url::Url::parse(&server.uri()).unwrap(),
+            String::from(MOCK_TOKEN),
+        ))
+        .unwrap();
+
+        // Get
+        assert_eq!(
+            client.get_varset_id("org", "existing").await.unwrap(),
+            Some(existing_id)
+        );
+        assert_eq!(client.get_varset_id("org", "new").await.unwrap(), None);
+
+        // Create
+        let varset_id = client.create_varset("org", "new").await.unwrap();
+        assert_eq!(
+            client.get_varset_id("org", "new").await.unwrap(),
+            Some(varset_id.clone())
+        );
+
+        // Apply, twice to check existing ones are left as they are
+        for _ in 0..2 {
+            client
+                .apply_varset_to_workspaces(&varset_id, std::slice::from_ref(&workspace_id))
+                .await
+                .unwrap();
+            client
+                .apply_varset_to_projects(&varset_id, std::slice::from_ref(&project_id))
+                .await
+                .unwrap();
+        }
+        assert_eq!(server.variable_set_workspaces(&varset_id), vec![
+            workspace_id.clone()
+        ]);
+        assert_eq!(server.variable_set_projects(&varset_id), vec![
+            project_id.clone()
+        ]);
+        let varset = client.get_varset("org", "new").await.unwrap().unwrap();
+        assert_eq!(varset.get_related_ids("workspaces"), vec![workspace_id]);
+        assert_eq!(varset.get_related_ids("projects"), vec![project_id]);
+
+        // No such workspace
+        let res = client
+            .apply_varset_to_workspaces(&varset_id, &[String::from("ws-none")])
+            .await;
+        assert!(matches!(
+            res,
+            Err(TfvarExportError::Api { status: 404, .. })
+        ));
+    }
+}
@@ -1,4 +1,9 @@
 pub mod clap;
+pub mod confirm;
 pub mod construct_export_value;
+pub mod diff;
 pub mod get_outputs;
+pub mod json_file;
+pub mod name_pattern;
+pub mod ownership;
 pub mod read_export_list;
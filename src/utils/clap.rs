@@ -1,81 +1,291 @@
 use clap::{crate_description, crate_name, crate_version, Arg, ArgAction, Command};
 
-pub fn new_clap_command() -> clap::ArgMatches {
+/// Options of connecting to Terraform API and logging, shared with `apply` and `restore`
+const COMMON_ARGS: [&str; 7] = [
+    "base_url",
+    "disable_log",
+    "connect_timeout",
+    "request_timeout",
+    "max_attempts",
+    "rate_limit",
+    "concurrency",
+];
+
+pub fn new_clap_command() -> Command {
     Command::new(crate_name!())
         .about(crate_description!())
         .version(crate_version!())
-        .arg(
-            Arg::new("base_url")
-                .short('b')
-                .long("base-url")
-                .default_value("https://app.terraform.io")
-                .require_equals(false)
-                .required(false)
-                .value_name("BASE_URL")
-                .help("Base URL of Terraform API"),
-        )
-        .arg(
-            Arg::new("target_workspaces")
-                .short('t')
-                .long("target-workspaces")
-                .require_equals(false)
-                .required(false)
-                .required_unless_present("show_workspaces")
-                .value_name("WORKSPACE_NAME1,WORKSPACE_NAME2,...")
-                .help(
-                    "Comma separated Terraform Cloud workspace names.\nRequired unless \
-                     `--show-workspaces` is set.",
+        .args_conflicts_with_subcommands(true)
+        .args(export_args())
+        .subcommand(
+            Command::new("plan")
+                .about(
+                    "Show the change of each variable without writing anything, and save it to a \
+                     file with `--out` to apply it later.",
+                )
+                .args(
+                    export_args()
+                        .into_iter()
+                        .map(|arg| match arg.get_id().as_str() {
+                            "show_workspaces" | "dry_run" | "auto_approve" | "backup_dir" => {
+                                arg.hide(true)
+                            },
+                            _ => arg,
+                        }),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_name("PATH_TO_PLAN_FILE")
+                        .help(
+                            "Save the plan to the file.\nThe file includes the values of \
+                             sensitive variables.",
+                        ),
                 ),
         )
-        .arg(
-            Arg::new("disable_log")
-                .short('q')
-                .long("disable-log")
-                .action(ArgAction::SetTrue)
-                .help(
-                    "Disable `Info` and `Warn` log.\nNote that `Error` log is always enabled \
-                     regardless of this flag.",
+        .subcommand(
+            Command::new("apply")
+                .about(
+                    "Apply a plan saved with `plan --out`, refusing to proceed if the variables \
+                     have changed since then.",
+                )
+                .args(export_args().into_iter().filter(|arg| {
+                    COMMON_ARGS.contains(&arg.get_id().as_str())
+                        || ["auto_approve", "backup_dir"].contains(&arg.get_id().as_str())
+                }))
+                .arg(
+                    Arg::new("plan_file")
+                        .index(1)
+                        .required(true)
+                        .value_name("PATH_TO_PLAN_FILE")
+                        .help("Path to the plan file saved with `plan --out`."),
                 ),
         )
-        .arg(
-            Arg::new("show_workspaces")
-                .short('w')
-                .conflicts_with_all([
-                    "export_list",
-                    "target_workspaces",
-                    "allow_update",
-                    "output_values_file",
-                    "export_list",
-                ])
-                .long("show-workspaces")
-                .action(ArgAction::SetTrue)
-                .help("Show available workspaces."),
-        )
-        .arg(
-            Arg::new("allow_update")
-                .short('u')
-                .long("allow-update")
-                .action(ArgAction::SetTrue)
-                .help("Allow update of existing values."),
-        )
-        .arg(
-            Arg::new("output_values_file")
-                .index(1)
-                .required(false)
-                .required_unless_present("show_workspaces")
-                .value_name("PATH_TO_OUTPUT_VALUES_FILE")
-                .help(
-                    "Path to the output values file generated with\n`terraform output \
-                     --json`.\nRequired unless `--show-workspaces` is set.",
+        .subcommand(
+            Command::new("restore")
+                .about(
+                    "Restore the variables backed up before exporting; deleted ones are created \
+                     again, changed ones are updated and ones exported since then are deleted.",
+                )
+                .args(export_args().into_iter().filter(|arg| {
+                    COMMON_ARGS.contains(&arg.get_id().as_str()) || arg.get_id() == "auto_approve"
+                }))
+                .arg(
+                    Arg::new("backup_file")
+                        .index(1)
+                        .required(true)
+                        .value_name("PATH_TO_BACKUP_FILE")
+                        .help("Path to the backup file saved before exporting."),
                 ),
         )
-        .arg(
-            Arg::new("export_list")
-                .index(2)
-                .required(false)
-                .required_unless_present("show_workspaces")
-                .value_name("PATH_TO_EXPORT_LIST")
-                .help("Path to the export list.\nRequired unless `--show-workspaces` is set."),
-        )
-        .get_matches()
+}
+
+/// Options of exporting values
+fn export_args() -> Vec<Arg> {
+    vec![
+        Arg::new("base_url")
+            .short('b')
+            .long("base-url")
+            .default_value("https://app.terraform.io")
+            .require_equals(false)
+            .required(false)
+            .value_name("BASE_URL")
+            .help("Base URL of Terraform API"),
+        Arg::new("target_workspaces")
+            .short('t')
+            .long("target-workspaces")
+            .require_equals(false)
+            .required(false)
+            .required_unless_present_any([
+                "show_workspaces",
+                "target_projects",
+                "target_tags",
+                "target_pattern",
+                "target_regex",
+                "varset",
+            ])
+            .value_name("WORKSPACE_NAME1,WORKSPACE_NAME2,...")
+            .help(
+                "Comma separated Terraform Cloud workspace names or IDs (`ws-...`).\nRequired \
+                 unless `--show-workspaces`, `--varset` or another `--target-*` option is set.",
+            ),
+        Arg::new("target_projects")
+            .long("target-projects")
+            .value_name("PROJECT_NAME1,PROJECT_NAME2,...")
+            .help(
+                "Comma separated Terraform Cloud project names.\nEvery workspace in the projects \
+                 is targeted.",
+            ),
+        Arg::new("target_tags")
+            .long("target-tags")
+            .value_name("TAG1,TAG2,...")
+            .help(
+                "Comma separated workspace tags; `key=value` for key-value tags.\nWorkspaces \
+                 having all the tags are targeted, narrowed down by `--target-pattern` and \
+                 `--target-regex` if set.",
+            ),
+        Arg::new("target_pattern")
+            .long("target-pattern")
+            .action(ArgAction::Append)
+            .value_name("GLOB")
+            .help(
+                "Glob pattern of workspace names such as `app-*-prod`.\nWorkspaces matching any \
+                 `--target-pattern` or `--target-regex` are targeted. Can be repeated.",
+            ),
+        Arg::new("target_regex")
+            .long("target-regex")
+            .action(ArgAction::Append)
+            .value_name("REGEX")
+            .help("Regex pattern of workspace names. Can be repeated."),
+        Arg::new("exclude_workspaces")
+            .long("exclude-workspaces")
+            .value_name("WORKSPACE_NAME1,WORKSPACE_NAME2,...")
+            .help("Comma separated workspace names excluded from the targeted workspaces."),
+        Arg::new("varset")
+            .long("varset")
+            .value_name("VARSET_NAME")
+            .help("Name of the variable set to export values to.\nCreated if it does not exist."),
+        Arg::new("varset_workspaces")
+            .long("varset-workspaces")
+            .requires("varset")
+            .value_name("WORKSPACE_NAME1,WORKSPACE_NAME2,...")
+            .help("Comma separated workspace names to apply the variable set to."),
+        Arg::new("varset_projects")
+            .long("varset-projects")
+            .requires("varset")
+            .value_name("PROJECT_NAME1,PROJECT_NAME2,...")
+            .help("Comma separated project names to apply the variable set to."),
+        Arg::new("disable_log")
+            .short('q')
+            .long("disable-log")
+            .action(ArgAction::SetTrue)
+            .help(
+                "Disable `Info` and `Warn` log.\nNote that `Error` log is always enabled \
+                 regardless of this flag.",
+            ),
+        Arg::new("show_workspaces")
+            .short('w')
+            .conflicts_with_all([
+                "export_list",
+                "target_workspaces",
+                "target_projects",
+                "target_tags",
+                "target_pattern",
+                "target_regex",
+                "exclude_workspaces",
+                "varset",
+                "allow_update",
+                "adopt",
+                "prune",
+                "dry_run",
+                "auto_approve",
+                "source",
+                "output_values_file",
+            ])
+            .long("show-workspaces")
+            .action(ArgAction::SetTrue)
+            .help("Show available workspaces."),
+        Arg::new("allow_update")
+            .short('u')
+            .long("allow-update")
+            .action(ArgAction::SetTrue)
+            .help("Allow update of existing values."),
+        Arg::new("adopt")
+            .long("adopt")
+            .requires("allow_update")
+            .action(ArgAction::SetTrue)
+            .help(
+                "Update existing variables not created by this tool from the same source as well \
+                 and take them over.",
+            ),
+        Arg::new("source")
+            .long("source")
+            .required_unless_present("show_workspaces")
+            .value_name("SOURCE_NAME")
+            .help(
+                "Name of the source recorded in the ownership marker of exported variables, \
+                 unique to the stack such as `network-prod`.\nOnly variables created from the \
+                 same source are updated or deleted. Required unless `--show-workspaces` is set.",
+            ),
+        Arg::new("prune")
+            .long("prune")
+            .action(ArgAction::SetTrue)
+            .help(
+                "Delete variables created by this tool from the same source but no longer in the \
+                 export list.\nThe other variables are never deleted.",
+            ),
+        Arg::new("dry_run")
+            .long("dry-run")
+            .action(ArgAction::SetTrue)
+            .help("Show the change of each variable and exit without writing anything."),
+        Arg::new("auto_approve")
+            .long("auto-approve")
+            .action(ArgAction::SetTrue)
+            .help(
+                "Write without asking for the confirmation.\nRequired when stdin is not a \
+                 terminal.",
+            ),
+        Arg::new("backup_dir")
+            .long("backup-dir")
+            .value_name("DIR")
+            .help(
+                "Directory to save the backup of the variables of the targets to before they are \
+                 changed.\nDefaults to `tfvar-export/backups` in `$XDG_STATE_HOME` or \
+                 `~/.local/state`. The backup includes values in plaintext except for sensitive \
+                 variables, which are not backed up.",
+            ),
+        Arg::new("connect_timeout")
+            .long("connect-timeout")
+            .default_value("10")
+            .value_parser(clap::value_parser!(u64))
+            .value_name("SECONDS")
+            .help("Timeout in seconds for connecting to Terraform API"),
+        Arg::new("request_timeout")
+            .long("request-timeout")
+            .default_value("30")
+            .value_parser(clap::value_parser!(u64))
+            .value_name("SECONDS")
+            .help("Timeout in seconds for each request to Terraform API"),
+        Arg::new("max_attempts")
+            .long("max-attempts")
+            .default_value("5")
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .value_name("N")
+            .help(
+                "Max number of attempts for each request to Terraform API.\nRequests responded \
+                 with `429` or failed to connect are retried with exponential backoff, and so are \
+                 `5xx` and timeouts except for creations.",
+            ),
+        Arg::new("rate_limit")
+            .long("rate-limit")
+            .default_value("20")
+            .value_parser(clap::value_parser!(u64).range(1..))
+            .value_name("REQUESTS_PER_SECOND")
+            .help("Max requests per second to Terraform API shared by all workspaces"),
+        Arg::new("concurrency")
+            .short('c')
+            .long("concurrency")
+            .default_value("1")
+            .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+            .value_name("N")
+            .help(
+                "Number of workspaces processed in parallel.\nRequests are still limited by \
+                 `--rate-limit`.",
+            ),
+        Arg::new("output_values_file")
+            .index(1)
+            .required(false)
+            .required_unless_present("show_workspaces")
+            .value_name("PATH_TO_OUTPUT_VALUES_FILE")
+            .help(
+                "Path to the output values file generated with\n`terraform output \
+                 --json`.\nRequired unless `--show-workspaces` is set.",
+            ),
+        Arg::new("export_list")
+            .index(2)
+            .required(false)
+            .required_unless_present("show_workspaces")
+            .value_name("PATH_TO_EXPORT_LIST")
+            .help("Path to the export list.\nRequired unless `--show-workspaces` is set."),
+    ]
 }
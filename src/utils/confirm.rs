@@ -0,0 +1,54 @@
+//! Confirmation before writing, in the spirit of `terraform apply`.
+
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Whether the confirmation can be asked; only if stdin is a terminal.
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal()
+}
+
+/// Ask `question` for the approval of the changes shown above; only `yes` is accepted.
+pub fn ask_approval(
+    question: &str,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> std::io::Result<bool> {
+    write!(
+        output,
+        "\n{}\n  Only 'yes' will be accepted to approve.\n\n  Enter a value: ",
+        question
+    )?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    writeln!(output)?;
+
+    Ok(answer.trim() == "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ask_approval() {
+        let ask = |answer: &str| {
+            let mut output = Vec::new();
+            let approved =
+                ask_approval("Do you want to?", &mut answer.as_bytes(), &mut output).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            assert!(output.contains("Do you want to?"));
+            assert!(output.contains("Only 'yes' will be accepted"));
+            approved
+        };
+
+        assert!(ask("yes\n"));
+        assert!(ask("  yes  \n"));
+        assert!(!ask("y\n"));
+        assert!(!ask("YES\n"));
+        assert!(!ask("no\n"));
+        // EOF
+        assert!(!ask(""));
+    }
+}
@@ -2,13 +2,41 @@
 
 use std::collections::HashMap;
 
-use crate::utils::{get_outputs::get_outputs, read_export_list::read_export_list};
+use crate::{
+    error::TfvarExportError,
+    terraform_api::{models::VariableCategory, register_variable::ValueEncoding},
+    utils::{
+        get_outputs::{get_outputs, OutputValue},
+        read_export_list::read_export_list,
+    },
+};
 
-#[derive(Debug, PartialEq)]
+#[derive(PartialEq)]
 pub struct ExportValue {
     variable_name: String,
     variable_description: Option<String>,
     value: serde_json::Value,
+    category: VariableCategory,
+    sensitive: bool,
+    encoding: ValueEncoding,
+}
+
+/// The value of a sensitive variable is never printed.
+impl std::fmt::Debug for ExportValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value: &dyn std::fmt::Debug = match self.sensitive {
+            true => &"(sensitive value)",
+            false => &self.value,
+        };
+        f.debug_struct("ExportValue")
+            .field("variable_name", &self.variable_name)
+            .field("variable_description", &self.variable_description)
+            .field("value", value)
+            .field("category", &self.category)
+            .field("sensitive", &self.sensitive)
+            .field("encoding", &self.encoding)
+            .finish()
+    }
 }
 
 impl ExportValue {
@@ -23,29 +51,85 @@ impl ExportValue {
     pub fn get_value(&self) -> &serde_json::Value {
         &self.value
     }
+
+    pub fn get_category(&self) -> VariableCategory {
+        self.category
+    }
+
+    pub fn is_sensitive(&self) -> bool {
+        self.sensitive
+    }
+
+    pub fn get_encoding(&self) -> ValueEncoding {
+        self.encoding
+    }
+
+    #[cfg(test)]
+    pub fn new(
+        variable_name: String,
+        variable_description: Option<String>,
+        value: serde_json::Value,
+    ) -> Self {
+        Self {
+            variable_name,
+            variable_description,
+            value,
+            category: VariableCategory::default(),
+            sensitive: false,
+            encoding: ValueEncoding::default(),
+        }
+    }
 }
 
 /// Construct a vector of values for exporting
 /// by mapping the output value and the variable name.
+///
+/// The values are in the order of the export list. A sensitive output is exported only if its entry
+/// opts in with `sensitive=true`; otherwise it is skipped with a warning.
 pub fn construct_export_value(
     file_path_export_list: &str,
     file_path_output: &str,
-) -> Result<Vec<ExportValue>, Box<dyn std::error::Error>> {
-    let export_list = read_export_list(file_path_export_list)?.unwrap();
-    let output_value: HashMap<String, serde_json::Value> = get_outputs(file_path_output)?
-        .iter()
-        .map(|val| (val.get_name().to_owned(), val.get_value().to_owned()))
+) -> Result<Vec<ExportValue>, TfvarExportError> {
+    let export_list = read_export_list(file_path_export_list)?.ok_or_else(|| {
+        TfvarExportError::Validation(format!(
+            "No valid entries were found in `{}`.",
+            file_path_export_list
+        ))
+    })?;
+    let output_value: HashMap<String, OutputValue> = get_outputs(file_path_output)?
+        .into_iter()
+        .map(|val| (val.get_name().to_owned(), val))
         .collect();
 
     // Merge values
-    let result = export_list
-        .iter()
-        .map(|(output_name, (var_name, opt_description))| ExportValue {
-            variable_name: var_name.to_owned(),
-            variable_description: opt_description.to_owned(),
-            value: output_value.get(output_name).unwrap().to_owned(),
-        })
-        .collect();
+    let mut result = Vec::new();
+    for export_entry in export_list.iter() {
+        let output_name = export_entry.get_output_name();
+        let output = output_value.get(output_name).ok_or_else(|| {
+            TfvarExportError::Validation(format!(
+                "Output `{}` in `{}` was not found in `{}`.",
+                output_name, file_path_export_list, file_path_output
+            ))
+        })?;
+        if output.is_sensitive() && !export_entry.is_sensitive() {
+            log::warn!(
+                "Output `{}` is sensitive and skipped. Add `sensitive=true` to its entry in `{}` \
+                 to export it as a sensitive variable.",
+                output_name,
+                file_path_export_list
+            );
+            continue;
+        }
+
+        result.push(ExportValue {
+            variable_name: export_entry.get_variable_name().to_owned(),
+            variable_description: export_entry.get_variable_description().to_owned(),
+            value: output.get_value().to_owned(),
+            category: export_entry.get_category(),
+            sensitive: export_entry.is_sensitive(),
+            encoding: export_entry.get_encoding(),
+        });
+    }
 
     Ok(result)
 }
@@ -63,21 +147,75 @@ mod tests {
 
         let result = construct_export_value(file_path_export_list, file_path_output).unwrap();
 
-        assert!(result.contains(&ExportValue {
-            variable_name: String::from("number_0_out"),
-            variable_description: None,
-            value: json!(0),
-        }));
-        assert!(result.contains(&ExportValue {
-            variable_name: String::from("string_out"),
-            variable_description: Some(String::from("string_description")),
-            value: json!("aaa"),
-        }));
-        assert!(result.contains(&ExportValue {
-            variable_name: String::from("set_of_object_out"),
-            variable_description: Some(String::from("set_of_object_description")),
-            value: json!([{"name":"aaa","type":"bbb"}]),
-        }));
-        assert!(result.len() == 3);
+        // In the order of the export list
+        assert_eq!(result, vec![
+            ExportValue {
+                variable_name: String::from("number_0_out"),
+                variable_description: None,
+                value: json!(0),
+                category: VariableCategory::Terraform,
+                sensitive: false,
+                encoding: ValueEncoding::Auto,
+            },
+            ExportValue {
+                variable_name: String::from("string_out"),
+                variable_description: Some(String::from("string_description")),
+                value: json!("aaa"),
+                category: VariableCategory::Terraform,
+                sensitive: false,
+                encoding: ValueEncoding::Auto,
+            },
+            ExportValue {
+                variable_name: String::from("set_of_object_out"),
+                variable_description: Some(String::from("set_of_object_description")),
+                value: json!([{"name":"aaa","type":"bbb"}]),
+                category: VariableCategory::Terraform,
+                sensitive: false,
+                encoding: ValueEncoding::Auto,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_construct_export_value_missing_output() {
+        // `no_line` has no entries
+        let result = construct_export_value(
+            "files/test/export_list.no_line.txt",
+            "files/test/outputs.json",
+        );
+        assert!(matches!(result, Err(TfvarExportError::Validation(_))));
+
+        // Not in the outputs
+        let result = construct_export_value(
+            "files/test/export_list_missing_output.txt",
+            "files/test/outputs.json",
+        );
+        assert!(matches!(result, Err(TfvarExportError::Validation(_))));
+    }
+
+    #[test]
+    fn test_construct_export_value_sensitive() {
+        // Skipped unless opted in
+        let result = construct_export_value(
+            "files/test/export_list_sensitive_not_opted_in.txt",
+            "files/test/outputs.json",
+        )
+        .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result.first().unwrap().get_variable_name(), "string_out");
+
+        // Opted in
+        let result = construct_export_value(
+            "files/test/export_list.with_options.txt",
+            "files/test/outputs.json",
+        )
+        .unwrap();
+        let secret = result
+            .iter()
+            .find(|val| val.get_variable_name() == "SECRET")
+            .unwrap();
+        assert!(secret.is_sensitive());
+        assert_eq!(secret.get_value(), &json!("**************"));
+        assert!(!format!("{:?}", secret).contains("**************"));
     }
 }
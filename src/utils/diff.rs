@@ -0,0 +1,96 @@
+//! Line-based diff of variable values.
+
+use std::io::IsTerminal;
+
+use crate::terraform_api::register_variable::parse_value;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether to color the output; only if stdout is a terminal and `NO_COLOR` is not set.
+pub fn use_color() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Pretty-print a variable value so that each element of a structure is on its own line.
+///
+/// HCL values are formatted as HCL and the others as JSON if they are valid; otherwise the value
+/// is returned as it is. Keys of objects are sorted, so the same values are formatted the same
+/// regardless of how they were written.
+pub fn pretty_value(value: &str, is_hcl: bool) -> String {
+    let pretty = match is_hcl {
+        true => parse_value(value, true)
+            .and_then(|val| hcl::value::to_value(val).ok())
+            .and_then(|val| hcl::format::to_string(&val).ok()),
+        false => parse_value(value, false)
+            .filter(|val| val.is_array() || val.is_object())
+            .and_then(|val| serde_json::to_string_pretty(&val).ok()),
+    };
+
+    pretty.unwrap_or_else(|| value.to_string())
+}
+
+/// Render a line-based diff from `old` to `new`.
+///
+/// Removed lines are prefixed with `-`, added ones with `+` and the others with a space. Removed
+/// and added lines are colored red and green if `color` is set.
+pub fn render_diff(old: &str, new: &str, color: bool) -> Vec<String> {
+    similar::TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| {
+            let line = change.value().trim_end_matches('\n');
+            let (sign, style) = match change.tag() {
+                similar::ChangeTag::Delete => ('-', RED),
+                similar::ChangeTag::Insert => ('+', GREEN),
+                similar::ChangeTag::Equal => (' ', ""),
+            };
+            match color && !style.is_empty() {
+                true => format!("{}{} {}{}", style, sign, line, RESET),
+                false => format!("{} {}", sign, line),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pretty_value() {
+        assert_eq!(
+            pretty_value(r#"{"a":"aaa","b":["bbb"]}"#, true),
+            "{\n  \"a\" = \"aaa\"\n  \"b\" = [\n    \"bbb\"\n  ]\n}"
+        );
+        assert_eq!(
+            pretty_value(r#"{"a":"aaa","b":["bbb"]}"#, false),
+            "{\n  \"a\": \"aaa\",\n  \"b\": [\n    \"bbb\"\n  ]\n}"
+        );
+        assert_eq!(
+            pretty_value(r#"["aaa", "bbb"]"#, true),
+            "[\n  \"aaa\",\n  \"bbb\"\n]"
+        );
+        // Scalars and invalid values are left as they are.
+        assert_eq!(pretty_value("1.2345", false), "1.2345");
+        assert_eq!(pretty_value("aaa", false), "aaa");
+        assert_eq!(pretty_value("{ a =", true), "{ a =");
+    }
+
+    #[test]
+    fn test_render_diff() {
+        let old = pretty_value(r#"{"a":"aaa","b":"bbb"}"#, false);
+        let new = pretty_value(r#"{"a":"aaa","b":"ccc"}"#, false);
+        assert_eq!(render_diff(&old, &new, false), vec![
+            "  {",
+            "    \"a\": \"aaa\",",
+            "-   \"b\": \"bbb\"",
+            "+   \"b\": \"ccc\"",
+            "  }",
+        ]);
+        assert_eq!(render_diff("aaa", "bbb", true), vec![
+            "\x1b[31m- aaa\x1b[0m",
+            "\x1b[32m+ bbb\x1b[0m",
+        ]);
+    }
+}
@@ -2,11 +2,14 @@
 
 use std::io::{prelude::*, BufReader};
 
+use crate::error::TfvarExportError;
+
 #[derive(Debug, PartialEq, Eq)]
 /// Struct of output value
 pub struct OutputValue {
     name: String,
     value: serde_json::Value,
+    sensitive: bool,
 }
 
 impl OutputValue {
@@ -17,6 +20,10 @@ impl OutputValue {
     pub fn get_value(&self) -> &serde_json::Value {
         &self.value
     }
+
+    pub fn is_sensitive(&self) -> bool {
+        self.sensitive
+    }
 }
 
 /// Read outputs from a file generated with `terraform output --json`
@@ -24,22 +31,32 @@ impl OutputValue {
 ///
 /// ## Remark
 ///
-/// - `sensitive` outputs are ignored for security reason.
-pub fn get_outputs(file_path: &str) -> Result<Vec<OutputValue>, Box<dyn std::error::Error>> {
-    let output_values_file = std::fs::File::open(file_path)?;
+/// - `sensitive` outputs are returned as well, so they must be exported only if opted in.
+pub fn get_outputs(file_path: &str) -> Result<Vec<OutputValue>, TfvarExportError> {
+    let io_error = |source| TfvarExportError::Io {
+        path: file_path.to_string(),
+        source,
+    };
+    let output_values_file = std::fs::File::open(file_path).map_err(io_error)?;
     let mut buf_reader = BufReader::new(output_values_file);
     let mut contents = String::new();
-    buf_reader.read_to_string(&mut contents)?;
+    buf_reader.read_to_string(&mut contents).map_err(io_error)?;
 
-    let contents_json: serde_json::Value = serde_json::from_str(&contents)?;
+    let contents_json: serde_json::Value = serde_json::from_str(&contents)
+        .map_err(|err| TfvarExportError::Parse(format!("`{}`: {}", file_path, err)))?;
     let output_values: Vec<OutputValue> = contents_json
         .as_object()
-        .unwrap()
+        .ok_or_else(|| {
+            TfvarExportError::Parse(format!(
+                "`{}` is not an output of `terraform output --json`.",
+                file_path
+            ))
+        })?
         .into_iter()
-        .filter(|val| val.1["sensitive"] == false) // Opt out `sensitive` elements.
         .map(|val| OutputValue {
             name: val.0.to_string(),
             value: val.1["value"].clone(),
+            sensitive: val.1["sensitive"] != false,
         })
         .collect();
 
@@ -55,47 +72,62 @@ mod tests {
     #[test]
     fn test_get_outputs() {
         let test_file = "files/test/outputs.json";
-        let res = get_outputs(&test_file).unwrap();
+        let res = get_outputs(test_file).unwrap();
         assert_eq!(res, vec![
             OutputValue {
                 name: String::from("bool"),
                 value: json!(false),
+                sensitive: false,
             },
             OutputValue {
                 name: String::from("list_of_object"),
                 value: json!({"a":"aaa","b":"bbb","c":null}),
+                sensitive: false,
             },
             OutputValue {
                 name: String::from("map_of_string"),
                 value: json!({"a":"aaa","b":"bbb","c":"ccc"}),
+                sensitive: false,
             },
             OutputValue {
                 name: String::from("number_0"),
                 value: json!(0),
+                sensitive: false,
             },
             OutputValue {
                 name: String::from("number_float"),
                 value: json!(1.2345),
+                sensitive: false,
             },
             OutputValue {
                 name: String::from("number_negative"),
                 value: json!(-1.2345),
+                sensitive: false,
+            },
+            OutputValue {
+                name: String::from("sensitive"),
+                value: json!("**************"),
+                sensitive: true,
             },
             OutputValue {
                 name: String::from("set_of_object"),
                 value: json!([{"name":"aaa","type":"bbb"}]),
+                sensitive: false,
             },
             OutputValue {
                 name: String::from("string"),
                 value: json!("aaa"),
+                sensitive: false,
             },
             OutputValue {
                 name: String::from("string_with_quote"),
                 value: json!("aaa\"bbb"),
+                sensitive: false,
             },
             OutputValue {
                 name: String::from("tuple"),
                 value: json!(["aaa", "bbb"]),
+                sensitive: false,
             },
         ])
     }
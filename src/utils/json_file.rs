@@ -0,0 +1,36 @@
+//! JSON files written and read by tfvar-export, such as plans and backups.
+
+use std::io::Write;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::TfvarExportError;
+
+/// Write `value` to `path` as pretty JSON, readable only by the owner on Unix.
+pub fn save<T: Serialize>(path: &str, value: &T) -> Result<(), TfvarExportError> {
+    let io_error = |source| TfvarExportError::Io {
+        path: path.to_string(),
+        source,
+    };
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).map_err(io_error)?;
+    file.write_all(serde_json::to_string_pretty(value)?.as_bytes())
+        .map_err(io_error)?;
+
+    Ok(())
+}
+
+/// Read a value saved by [`save`].
+pub fn load<T: DeserializeOwned>(path: &str) -> Result<T, TfvarExportError> {
+    let content = std::fs::read_to_string(path).map_err(|source| TfvarExportError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+
+    serde_json::from_str(&content)
+        .map_err(|err| TfvarExportError::Parse(format!("`{}`: {}", path, err)))
+}
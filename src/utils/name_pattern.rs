@@ -0,0 +1,89 @@
+//! Match workspace names with glob or regex patterns.
+
+use crate::error::TfvarExportError;
+
+/// Pattern of names
+#[derive(Debug, Clone)]
+pub struct NamePattern {
+    pattern: String,
+    regex: regex::Regex,
+}
+
+impl NamePattern {
+    /// Build from a glob pattern, where `*` matches any characters and `?` matches a character.
+    ///
+    /// The whole name must match.
+    pub fn from_glob(pattern: &str) -> Result<Self, TfvarExportError> {
+        let mut regex = String::from("^");
+        for c in pattern.chars() {
+            match c {
+                '*' => regex.push_str(".*"),
+                '?' => regex.push('.'),
+                _ => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push('$');
+
+        Self::new(pattern, &regex)
+    }
+
+    /// Build from a regex pattern, which matches any part of a name unless anchored.
+    pub fn from_regex(pattern: &str) -> Result<Self, TfvarExportError> {
+        Self::new(pattern, pattern)
+    }
+
+    fn new(pattern: &str, regex: &str) -> Result<Self, TfvarExportError> {
+        let regex = regex::Regex::new(regex).map_err(|err| {
+            TfvarExportError::Config(format!("Invalid pattern `{}`: {}", pattern, err))
+        })?;
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+
+    pub fn get_pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_pattern_glob() {
+        let pattern = NamePattern::from_glob("app-*-prod").unwrap();
+        assert!(pattern.is_match("app-web-prod"));
+        assert!(pattern.is_match("app--prod"));
+        assert!(!pattern.is_match("app-web-prod-old"));
+        assert!(!pattern.is_match("my-app-web-prod"));
+
+        let pattern = NamePattern::from_glob("ws-?.x").unwrap();
+        assert!(pattern.is_match("ws-1.x"));
+        // `.` is not a wildcard
+        assert!(!pattern.is_match("ws-1-x"));
+        assert!(!pattern.is_match("ws-10.x"));
+    }
+
+    #[test]
+    fn test_name_pattern_regex() {
+        let pattern = NamePattern::from_regex("^app-(web|api)-prod$").unwrap();
+        assert!(pattern.is_match("app-api-prod"));
+        assert!(!pattern.is_match("app-db-prod"));
+
+        // Unanchored
+        let pattern = NamePattern::from_regex("prod").unwrap();
+        assert!(pattern.is_match("app-web-prod-old"));
+
+        assert!(matches!(
+            NamePattern::from_regex("app-("),
+            Err(TfvarExportError::Config(_))
+        ));
+    }
+}
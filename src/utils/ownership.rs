@@ -0,0 +1,95 @@
+//! Ownership marker of variables managed by tfvar-export.
+//!
+//! The marker is appended to the description of a variable and records the source of the value,
+//! so that variables created by hand or exported from another source are never updated nor deleted
+//! unless adopted explicitly.
+
+/// Beginning of the marker, followed by the source and `]`
+const MARKER_PREFIX: &str = "[managed by tfvar-export from ";
+
+/// Ownership marker of a source
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct OwnershipMarker {
+    source: String,
+}
+
+impl OwnershipMarker {
+    pub fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+        }
+    }
+
+    /// Append the marker to a description, replacing the marker of another source if any.
+    pub fn mark_description(&self, description: Option<&str>) -> String {
+        let description = description.map(strip_marker).unwrap_or_default();
+        let marker = format!("{}{}]", MARKER_PREFIX, self.source);
+        match description.is_empty() {
+            true => marker,
+            false => format!("{} {}", description, marker),
+        }
+    }
+
+    /// Whether a description has the marker of this source.
+    pub fn is_owner(&self, description: Option<&str>) -> bool {
+        marker_source(description) == Some(self.source.as_str())
+    }
+}
+
+/// Source recorded in the marker of a description, or `None` if the description has no marker.
+pub fn marker_source(description: Option<&str>) -> Option<&str> {
+    let description = description?.strip_suffix(']')?;
+    let start = description.rfind(MARKER_PREFIX)?;
+
+    Some(&description[start + MARKER_PREFIX.len()..])
+}
+
+/// Remove the marker from a description.
+fn strip_marker(description: &str) -> &str {
+    match marker_source(Some(description)) {
+        Some(_) => description[..description.rfind(MARKER_PREFIX).unwrap()].trim_end(),
+        None => description,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ownership_marker() {
+        let marker = OwnershipMarker::new("network/outputs.json");
+        assert_eq!(
+            marker.mark_description(Some("description")),
+            "description [managed by tfvar-export from network/outputs.json]"
+        );
+        assert_eq!(
+            marker.mark_description(Some("")),
+            "[managed by tfvar-export from network/outputs.json]"
+        );
+        assert_eq!(
+            marker.mark_description(None),
+            "[managed by tfvar-export from network/outputs.json]"
+        );
+        // Marker of another source is replaced.
+        assert_eq!(
+            marker.mark_description(Some("description [managed by tfvar-export from other]")),
+            "description [managed by tfvar-export from network/outputs.json]"
+        );
+
+        let description = marker.mark_description(Some("description"));
+        assert_eq!(
+            marker_source(Some(&description)),
+            Some("network/outputs.json")
+        );
+        assert!(marker.is_owner(Some(&description)));
+        assert!(!OwnershipMarker::new("other").is_owner(Some(&description)));
+        assert!(!marker.is_owner(Some("description")));
+        assert!(!marker.is_owner(None));
+        assert_eq!(marker_source(Some("description")), None);
+        assert_eq!(
+            marker_source(Some("[managed by tfvar-export from x] by hand")),
+            None
+        );
+    }
+}
@@ -5,48 +5,173 @@ use std::{
     io::{prelude::*, BufReader},
 };
 
-/// Read export list and return a HashMap.
+use crate::{
+    error::TfvarExportError,
+    terraform_api::{models::VariableCategory, register_variable::ValueEncoding},
+};
+
+/// Export list entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportEntry {
+    output_name: String,
+    variable_name: String,
+    variable_description: Option<String>,
+    category: VariableCategory,
+    /// Registered as a sensitive variable. Required to export a sensitive output.
+    sensitive: bool,
+    encoding: ValueEncoding,
+}
+
+impl ExportEntry {
+    pub fn get_output_name(&self) -> &str {
+        &self.output_name
+    }
+
+    pub fn get_variable_name(&self) -> &str {
+        &self.variable_name
+    }
+
+    pub fn get_variable_description(&self) -> &Option<String> {
+        &self.variable_description
+    }
+
+    pub fn get_category(&self) -> VariableCategory {
+        self.category
+    }
+
+    pub fn is_sensitive(&self) -> bool {
+        self.sensitive
+    }
+
+    pub fn get_encoding(&self) -> ValueEncoding {
+        self.encoding
+    }
+}
+
+/// Export list entries in the order of the lines
+///
+/// An output can be exported to several variables, such as a Terraform variable and an
+/// environment variable, but a variable name and category appear only once.
+pub type ExportList = Vec<ExportEntry>;
+
+/// Read export list and return its entries.
 ///
 /// ## Remark
 ///
-/// Return a HashMap for searching efficiency.
-pub fn read_export_list(
-    file_path: &str,
-) -> Result<Option<HashMap<String, (String, Option<String>)>>, Box<dyn std::error::Error>> {
-    let file = std::fs::File::open(file_path).expect("Failed to open a file.");
+/// The order of the lines is kept, so plans of the same input are always in the same order. Lines
+/// exporting to the same variable name and category are rejected.
+pub fn read_export_list(file_path: &str) -> Result<Option<ExportList>, TfvarExportError> {
+    let io_error = |source| TfvarExportError::Io {
+        path: file_path.to_string(),
+        source,
+    };
+    let file = std::fs::File::open(file_path).map_err(io_error)?;
     let mut buf_reader = BufReader::new(file);
     let mut contents = String::new();
-    buf_reader.read_to_string(&mut contents)?;
-    contents = contents.trim().to_string(); // Trim leading and trailing empty lines
+    buf_reader.read_to_string(&mut contents).map_err(io_error)?;
 
-    let mut output: HashMap<String, (String, Option<String>)> = HashMap::new();
-    let mut lines = contents.lines();
+    let mut output: ExportList = Vec::new();
+    // Line numbers of the entries in `output`
+    let mut line_numbers: HashMap<(String, VariableCategory), usize> = HashMap::new();
 
     let mut entries = Vec::new();
-    while let Some(mut line) = lines.next() {
-        line = line.trim();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
         if line.is_empty() || line.starts_with("#") {
             // Skip an empty or a comment line
             continue;
         }
-        entries.push(line)
+        entries.push((i + 1, line))
     }
 
-    if entries.len() < 1 {
+    if entries.is_empty() {
         log::warn!("No valid entries were found in the export list.");
         return Ok(None);
     }
 
-    entries.into_iter().for_each(|entry| {
+    for (line_number, entry) in entries.into_iter() {
         let record: Vec<String> = entry.split(',').map(|val| val.to_string()).collect();
-        let source = record.get(0).expect("Failed to read entry.").to_owned();
-        let dest = record.get(1).expect("Failed to read entry.").to_owned();
-        let description = match record.get(2) {
-            Some(val) => Some(val.to_owned()),
-            None => None,
+        let source = record.first().unwrap().to_owned();
+        let dest = match record.get(1) {
+            Some(val) if !source.is_empty() && !val.is_empty() => val.to_owned(),
+            _ => {
+                return Err(TfvarExportError::Validation(format!(
+                    "`{}` in `{}` must be in the format of `<Output name>,<Variable \
+                     name>,<Variable description>[,<Option>=<Value>...]`.",
+                    entry, file_path
+                )))
+            },
+        };
+        let description = record.get(2).map(|val| val.to_owned());
+        if let Some(val) = description.as_deref() {
+            // Options given without the description would be taken as the description.
+            if let Some((option, _)) = val.split_once('=') {
+                if matches!(option.trim(), "category" | "sensitive" | "encoding") {
+                    return Err(TfvarExportError::Validation(format!(
+                        "`{}` in `{}`: The description `{}` looks like an option. Options follow \
+                         the description, which may be empty such as `{},{},,{}`.",
+                        entry,
+                        file_path,
+                        val,
+                        source,
+                        dest,
+                        val.trim()
+                    )));
+                }
+            }
+        }
+
+        let mut export_entry = ExportEntry {
+            output_name: source,
+            variable_name: dest,
+            variable_description: description,
+            category: VariableCategory::default(),
+            sensitive: false,
+            encoding: ValueEncoding::default(),
         };
-        output.insert(source, (dest, description));
-    });
+        // Options
+        for option in record.iter().skip(3) {
+            let invalid = |msg: String| {
+                TfvarExportError::Validation(format!("`{}` in `{}`: {}", entry, file_path, msg))
+            };
+            match option.trim().split_once('=') {
+                Some(("category", val)) => {
+                    export_entry.category = val.trim().parse().map_err(invalid)?
+                },
+                Some(("sensitive", val)) => {
+                    export_entry.sensitive = val.trim().parse().map_err(|_| {
+                        invalid(format!(
+                            "Invalid `sensitive` value `{}`, expected `true` or `false`.",
+                            val
+                        ))
+                    })?
+                },
+                Some(("encoding", val)) => {
+                    export_entry.encoding = val.trim().parse().map_err(invalid)?
+                },
+                _ => {
+                    return Err(invalid(format!(
+                        "Unknown option `{}`, expected `category=<terraform|env>`, \
+                         `sensitive=<true|false>` or `encoding=<auto|hcl|string|json-string>`.",
+                        option
+                    )))
+                },
+            }
+        }
+
+        let key = (
+            export_entry.get_variable_name().to_owned(),
+            export_entry.get_category(),
+        );
+        if let Some(line_number_exported) = line_numbers.get(&key) {
+            return Err(TfvarExportError::Validation(format!(
+                "Line {} of `{}`: `{}` ({}) is already exported by line {}.",
+                line_number, file_path, key.0, key.1, line_number_exported
+            )));
+        }
+        line_numbers.insert(key, line_number);
+        output.push(export_entry);
+    }
 
     Ok(Some(output))
 }
@@ -55,47 +180,139 @@ pub fn read_export_list(
 mod tests {
     use super::*;
 
+    fn entry(
+        output_name: &str,
+        variable_name: &str,
+        variable_description: Option<&str>,
+    ) -> ExportEntry {
+        ExportEntry {
+            output_name: output_name.to_string(),
+            variable_name: variable_name.to_string(),
+            variable_description: variable_description.map(|val| val.to_string()),
+            category: VariableCategory::Terraform,
+            sensitive: false,
+            encoding: ValueEncoding::Auto,
+        }
+    }
+
     #[test]
     fn test_read_export_list_succeed() {
         // Neat entries
         let path = "files/test/export_list.txt";
-        let resp = read_export_list(&path).unwrap();
-        let mut expected = HashMap::new();
-        expected.insert(
-            "number_float".to_string(),
-            (
-                "number_float_copy".to_string(),
-                Some("number_float_description".to_string()),
-            ),
+        let resp = read_export_list(path).unwrap();
+        assert_eq!(
+            resp.unwrap(),
+            vec![
+                entry(
+                    "number_float",
+                    "number_float_copy",
+                    Some("number_float_description"),
+                ),
+                entry("set_of_object", "set_of_object_copy", None),
+            ]
         );
-        expected.insert(
-            "set_of_object".to_string(),
-            ("set_of_object_copy".to_string(), None),
-        );
-        assert_eq!(resp.unwrap(), expected);
 
         // With empty lines
         let path = "files/test/export_list.with_empty_lines.txt";
-        let resp = read_export_list(&path).unwrap();
-        let mut expected = HashMap::new();
-        expected.insert(
-            "number_float".to_string(),
-            ("number_float_copy".to_string(), Some("".to_string())),
+        let resp = read_export_list(path).unwrap();
+        assert_eq!(
+            resp.unwrap(),
+            vec![
+                entry("number_float", "number_float_copy", Some("")),
+                entry(
+                    "set_of_object",
+                    "set_of_object_copy",
+                    Some("set_of_object_description"),
+                ),
+            ]
+        );
+
+        // With options, in the order of the lines
+        let path = "files/test/export_list.with_options.txt";
+        let resp = read_export_list(path).unwrap();
+        assert_eq!(
+            resp.unwrap(),
+            vec![
+                ExportEntry {
+                    category: VariableCategory::Env,
+                    ..entry("string", "STRING_ENV", Some("string_description"))
+                },
+                entry("number_0", "number_0_copy", Some("")),
+                ExportEntry {
+                    category: VariableCategory::Env,
+                    sensitive: true,
+                    ..entry("sensitive", "SECRET", Some(""))
+                },
+                ExportEntry {
+                    encoding: ValueEncoding::JsonString,
+                    ..entry("map_of_string", "map_of_string_json", Some(""))
+                },
+                ExportEntry {
+                    encoding: ValueEncoding::Hcl,
+                    ..entry("number_float", "number_float_hcl", Some(""))
+                },
+            ]
         );
-        expected.insert(
-            "set_of_object".to_string(),
-            (
-                "set_of_object_copy".to_string(),
-                Some("set_of_object_description".to_string()),
-            ),
+    }
+
+    #[test]
+    fn test_read_export_list_same_output() {
+        // An output exported as a Terraform variable and an environment variable of the same name
+        let path = "files/test/export_list.same_output.txt";
+        let resp = read_export_list(path).unwrap();
+        assert_eq!(
+            resp.unwrap(),
+            vec![
+                entry("string", "region", None),
+                ExportEntry {
+                    category: VariableCategory::Env,
+                    ..entry("string", "region", Some(""))
+                },
+            ]
         );
-        assert_eq!(resp.unwrap(), expected);
+    }
+
+    #[test]
+    fn test_read_export_list_duplicate() {
+        let path = "files/test/export_list.duplicate.txt";
+        let resp = read_export_list(path);
+        match resp {
+            Err(TfvarExportError::Validation(msg)) => {
+                assert!(msg.contains("Line 4 of"), "{}", msg);
+                assert!(msg.contains("by line 2"), "{}", msg);
+            },
+            _ => panic!("Duplicate entries should be rejected: {:?}", resp),
+        }
+    }
+
+    #[test]
+    fn test_read_export_list_invalid() {
+        // Missing file
+        let resp = read_export_list("files/test/no_such_file.txt");
+        assert!(matches!(resp, Err(TfvarExportError::Io { .. })));
+
+        // Invalid option
+        let resp = read_export_list("files/test/export_list.invalid_option.txt");
+        assert!(matches!(resp, Err(TfvarExportError::Validation(_))));
+
+        // Invalid encoding
+        let resp = read_export_list("files/test/export_list.invalid_encoding.txt");
+        assert!(matches!(resp, Err(TfvarExportError::Validation(_))));
+
+        // An option in place of the description
+        let resp = read_export_list("files/test/export_list.option_as_description.txt");
+        match resp {
+            Err(TfvarExportError::Validation(msg)) => {
+                assert!(msg.contains("`string,STRING_ENV,,category=env`"), "{}", msg)
+            },
+            _ => panic!("An option as the description should be rejected: {:?}", resp),
+        }
     }
 
     #[test]
     fn test_read_export_list_fail() {
         let path = "files/test/export_list.no_line.txt";
-        let resp = read_export_list(&path).unwrap();
+        let resp = read_export_list(path).unwrap();
         assert_eq!(resp, None);
     }
 }